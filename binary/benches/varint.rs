@@ -0,0 +1,233 @@
+//!
+//! Compares the varint implementation with the byte at a time implementation that it replaced, encoding
+//! and decoding buffers of 1024 varints of various sizes.
+//!
+
+use binary::datatypes::{VarI64, VarU32, U8};
+use binary::error::{DecodeError, EncodeError};
+use binary::reader::{Reader, SliceReader};
+use binary::Binary;
+use bytes::{BufMut, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::io::Write;
+
+const COUNT: usize = 1024;
+
+/// The previous implementation, which wrote and read every byte separately.
+mod legacy {
+    use super::*;
+
+    pub fn write_u32(mut u: u32, buf: &mut impl Write) -> Result<(), EncodeError> {
+        while u >= 0x80 {
+            U8::new(u as u8 | 0x80).serialize(buf)?;
+            u >>= 7;
+        }
+
+        U8::new(u as u8).serialize(buf)
+    }
+
+    pub fn read_u32<'a>(buf: &mut impl Reader<'a>) -> Result<u32, DecodeError> {
+        let offset = buf.position();
+        let mut v = 0;
+
+        for i in (0..35).step_by(7) {
+            let b = U8::deserialize(buf)?.0;
+            v |= ((b & 0x7f) as u32) << (i as u32);
+
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+
+        Err(DecodeError::VarIntOverflow {
+            offset,
+            max_bytes: 5,
+        })
+    }
+
+    pub fn write_i64(val: i64, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let mut ux = (val as u64) << 1;
+
+        if val < 0 {
+            ux = !ux;
+        }
+
+        while ux >= 0x80 {
+            U8::new(ux as u8 | 0x80).serialize(buf)?;
+            ux >>= 7;
+        }
+
+        U8::new(ux as u8).serialize(buf)
+    }
+
+    pub fn read_i64<'a>(buf: &mut impl Reader<'a>) -> Result<i64, DecodeError> {
+        let offset = buf.position();
+        let mut ux = 0u64;
+
+        for i in (0..70).step_by(7) {
+            let b = U8::deserialize(buf)?.0;
+            ux |= ((b & 0x7f) as u64) << i;
+
+            if b & 0x80 == 0 {
+                return Ok(((ux >> 1) as i64) ^ -((ux & 1) as i64));
+            }
+        }
+
+        Err(DecodeError::VarIntOverflow {
+            offset,
+            max_bytes: 10,
+        })
+    }
+}
+
+/// Values that take 1, 2 to 3, and 5 bytes when encoded.
+fn inputs() -> [(&'static str, Vec<u32>); 3] {
+    let mut seed = 0x2545F4914F6CDD1Du64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as u32
+    };
+
+    [
+        ("small", (0..COUNT).map(|_| next() % 0x80).collect()),
+        ("medium", (0..COUNT).map(|_| next() % 0x20_0000).collect()),
+        ("large", (0..COUNT).map(|_| next() | 0xF000_0000).collect()),
+    ]
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("varint_encode");
+    group.throughput(Throughput::Elements(COUNT as u64));
+
+    for (name, values) in inputs() {
+        let mut buf = Vec::with_capacity(COUNT * 10);
+
+        group.bench_with_input(
+            BenchmarkId::new("legacy_u32", name),
+            &values,
+            |b, values| {
+                b.iter(|| {
+                    buf.clear();
+                    for val in values {
+                        legacy::write_u32(black_box(*val), &mut buf).unwrap();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("u32", name), &values, |b, values| {
+            b.iter(|| {
+                buf.clear();
+                for val in values {
+                    VarU32::new(black_box(*val)).serialize(&mut buf).unwrap();
+                }
+            })
+        });
+        // Writers that do more work per call than `Vec`, such as this one, gain the most from the
+        // single write.
+        let mut writer = BytesMut::with_capacity(COUNT * 10).writer();
+
+        group.bench_with_input(
+            BenchmarkId::new("legacy_u32_buf_mut", name),
+            &values,
+            |b, values| {
+                b.iter(|| {
+                    writer.get_mut().clear();
+                    for val in values {
+                        legacy::write_u32(black_box(*val), &mut writer).unwrap();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("u32_buf_mut", name),
+            &values,
+            |b, values| {
+                b.iter(|| {
+                    writer.get_mut().clear();
+                    for val in values {
+                        VarU32::new(black_box(*val)).serialize(&mut writer).unwrap();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("legacy_i64", name),
+            &values,
+            |b, values| {
+                b.iter(|| {
+                    buf.clear();
+                    for val in values {
+                        legacy::write_i64(black_box(*val as i64), &mut buf).unwrap();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("i64", name), &values, |b, values| {
+            b.iter(|| {
+                buf.clear();
+                for val in values {
+                    VarI64::new(black_box(*val as i64))
+                        .serialize(&mut buf)
+                        .unwrap();
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("varint_decode");
+    group.throughput(Throughput::Elements(COUNT as u64));
+
+    for (name, values) in inputs() {
+        let mut u32s = Vec::new();
+        let mut i64s = Vec::new();
+
+        for val in &values {
+            VarU32::new(*val).serialize(&mut u32s).unwrap();
+            VarI64::new(*val as i64).serialize(&mut i64s).unwrap();
+        }
+
+        group.bench_with_input(BenchmarkId::new("legacy_u32", name), &u32s, |b, data| {
+            b.iter(|| {
+                let mut reader = SliceReader::new(black_box(data));
+                for _ in 0..COUNT {
+                    black_box(legacy::read_u32(&mut reader).unwrap());
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("u32", name), &u32s, |b, data| {
+            b.iter(|| {
+                let mut reader = SliceReader::new(black_box(data));
+                for _ in 0..COUNT {
+                    black_box(VarU32::deserialize(&mut reader).unwrap());
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("legacy_i64", name), &i64s, |b, data| {
+            b.iter(|| {
+                let mut reader = SliceReader::new(black_box(data));
+                for _ in 0..COUNT {
+                    black_box(legacy::read_i64(&mut reader).unwrap());
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("i64", name), &i64s, |b, data| {
+            b.iter(|| {
+                let mut reader = SliceReader::new(black_box(data));
+                for _ in 0..COUNT {
+                    black_box(VarI64::deserialize(&mut reader).unwrap());
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
@@ -0,0 +1,218 @@
+use crate::error::{DecodeError, EncodeError};
+use crate::prefixed::Prefix;
+use crate::reader::{Limits, Reader, SliceReader};
+use crate::Binary;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use tokio_bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Default maximum size of a single frame, not counting its length prefix.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 1 << 21;
+
+///
+/// FrameCodec streams `Binary` messages over a `tokio_util::codec::Framed` transport. Every message is
+/// written as a frame made of its encoded length, using the `Prefix` type `P`, followed by the message
+/// itself.
+///
+/// The decoder waits until a complete frame has been buffered before decoding it, and rejects frames over
+/// the maximum frame size as soon as their length prefix is read, without buffering them first. A frame
+/// must contain exactly one message, any bytes left over after decoding it are an error.
+///
+pub struct FrameCodec<P: Prefix, M> {
+    max_frame_len: usize,
+    limits: Limits,
+    _marker: PhantomData<fn() -> (P, M)>,
+}
+
+impl<P: Prefix, M> FrameCodec<P, M> {
+    /// Creates a new codec with the default maximum frame size and decode limits.
+    pub fn new() -> Self {
+        Self::with_max_frame_len(DEFAULT_MAX_FRAME_LEN)
+    }
+
+    /// Creates a new codec that rejects frames longer than `max_frame_len` bytes.
+    pub fn with_max_frame_len(max_frame_len: usize) -> Self {
+        Self {
+            max_frame_len,
+            limits: Limits::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the limits used when decoding the messages inside the frames.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the maximum size of a frame, not counting its length prefix.
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    fn check_frame_len(&self, len: usize) -> Result<(), CodecError> {
+        if len > self.max_frame_len {
+            return Err(CodecError::FrameTooLarge {
+                len,
+                max: self.max_frame_len,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: Prefix, M> Default for FrameCodec<P, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Prefix, M> Clone for FrameCodec<P, M> {
+    fn clone(&self) -> Self {
+        Self {
+            max_frame_len: self.max_frame_len,
+            limits: self.limits,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P: Prefix, M> std::fmt::Debug for FrameCodec<P, M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameCodec")
+            .field("max_frame_len", &self.max_frame_len)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl<P: Prefix, M: for<'a> Binary<'a>> Decoder for FrameCodec<P, M> {
+    type Item = M;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<M>, CodecError> {
+        let mut reader = SliceReader::with_limits(src, Limits::UNLIMITED);
+
+        let len = match P::decode(&mut reader) {
+            Ok(len) => len,
+            // The length prefix itself has not been received completely yet.
+            Err(DecodeError::UnexpectedEof { .. }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        self.check_frame_len(len)?;
+
+        let prefix_len = reader.position();
+        if reader.remaining() < len {
+            src.reserve(prefix_len + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(prefix_len);
+        let frame = src.split_to(len);
+
+        let mut reader = SliceReader::with_limits(&frame, self.limits);
+        let message = M::deserialize(&mut reader)?;
+
+        if reader.remaining() != 0 {
+            return Err(DecodeError::TrailingBytes {
+                offset: reader.position(),
+                remaining: reader.remaining(),
+            }
+            .into());
+        }
+
+        Ok(Some(message))
+    }
+}
+
+impl<'a, P: Prefix, M: Binary<'a>> Encoder<M> for FrameCodec<P, M> {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: M, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let len = item.encoded_len();
+        self.check_frame_len(len)?;
+
+        dst.reserve(P::encoded_len(len) + len);
+
+        // A frame that fails to encode is removed again, so that the frames written after it are not
+        // appended to a partial one.
+        let start = dst.len();
+        let mut writer = dst.writer();
+        let res = P::encode(len, &mut writer).and_then(|_| item.serialize(&mut writer));
+
+        if let Err(err) = res {
+            dst.truncate(start);
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// CodecError is returned by `FrameCodec` when reading or writing a frame fails.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The underlying transport returned an error.
+    Io(std::io::Error),
+    /// A frame, or its length prefix, could not be decoded.
+    Decode(DecodeError),
+    /// A message could not be encoded.
+    Encode(EncodeError),
+    /// A frame was longer than the maximum frame size of the codec.
+    FrameTooLarge { len: usize, max: usize },
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Decode(err) => write!(f, "{}", err),
+            Self::Encode(err) => write!(f, "{}", err),
+            Self::FrameTooLarge { len, max } => {
+                write!(f, "frame of {} bytes exceeds the maximum of {}", len, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Decode(err) => Some(err),
+            Self::Encode(err) => Some(err),
+            Self::FrameTooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CodecError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<DecodeError> for CodecError {
+    fn from(err: DecodeError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+impl From<EncodeError> for CodecError {
+    fn from(err: EncodeError) -> Self {
+        Self::Encode(err)
+    }
+}
+
+impl From<CodecError> for std::io::Error {
+    fn from(err: CodecError) -> Self {
+        match err {
+            CodecError::Io(err) => err,
+            CodecError::Decode(err) => err.into(),
+            CodecError::Encode(err) => err.into(),
+            err => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use crate::datatypes::{VarI32, VarU32, F32, U64, U8};
+use crate::error::{DecodeError, EncodeError};
+use crate::reader::Reader;
+use crate::Binary;
+use byteorder::LE;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// Vec2 is a vector of two floats, encoded as two `F32<LE>`. It is mostly used for rotations and for
+/// movement input.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Vec3 is a vector of three floats, encoded as three `F32<LE>`. It is used for positions, velocities
+/// and rotations of entities.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// BlockPos is the position of a block, encoded as a `VarI32` for x and z and a `VarU32` for y. Negative
+/// y coordinates are sent as their two's complement, so they still round trip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Uuid is a 128-bit UUID, encoded as its most significant and then its least significant half, each as
+/// a little endian u64.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Uuid(pub u128);
+
+/// RotationByte is an angle packed into a single byte as `degrees / 360 * 256`, used for the yaw and
+/// pitch of entities in some packets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct RotationByte(pub u8);
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2::new(0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Returns the position of the block that this position is in.
+    pub fn block_pos(self) -> BlockPos {
+        BlockPos::new(
+            self.x.floor() as i32,
+            self.y.floor() as i32,
+            self.z.floor() as i32,
+        )
+    }
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Returns the position moved by the provided amount of blocks on every axis.
+    pub const fn offset(self, x: i32, y: i32, z: i32) -> Self {
+        Self::new(self.x + x, self.y + y, self.z + z)
+    }
+
+    /// Returns the position of the center of the block.
+    pub fn center(self) -> Vec3 {
+        Vec3::from(self) + Vec3::new(0.5, 0.5, 0.5)
+    }
+}
+
+impl Uuid {
+    pub const NIL: Uuid = Uuid(0);
+
+    pub const fn new(val: u128) -> Self {
+        Self(val)
+    }
+
+    /// Creates a UUID from its most and least significant halves.
+    pub const fn from_u64_pair(high: u64, low: u64) -> Self {
+        Self(((high as u128) << 64) | low as u128)
+    }
+
+    /// Returns the most and least significant halves of the UUID.
+    pub const fn as_u64_pair(&self) -> (u64, u64) {
+        ((self.0 >> 64) as u64, self.0 as u64)
+    }
+
+    /// Creates a UUID from its 16 bytes in the order they are written in its text form.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(u128::from_be_bytes(bytes))
+    }
+
+    /// Returns the 16 bytes of the UUID in the order they are written in its text form.
+    pub const fn into_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl RotationByte {
+    pub fn new(val: u8) -> Self {
+        Self(val)
+    }
+
+    /// Packs an angle in degrees into a byte, wrapping it around into the range of a full turn.
+    pub fn from_degrees(degrees: f32) -> Self {
+        Self((degrees / 360.0 * 256.0).round().rem_euclid(256.0) as u8)
+    }
+
+    /// Returns the angle in degrees, between 0 and 360.
+    pub fn degrees(self) -> f32 {
+        self.0 as f32 / 256.0 * 360.0
+    }
+}
+
+impl Display for Uuid {
+    /// Formats the UUID in its hyphenated text form, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let val = self.0;
+
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            val >> 96,
+            (val >> 80) & 0xffff,
+            (val >> 64) & 0xffff,
+            (val >> 48) & 0xffff,
+            val & 0xffff_ffff_ffff
+        )
+    }
+}
+
+impl<'a> Binary<'a> for Vec2 {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        F32::<LE>::new(self.x).serialize(buf)?;
+        F32::<LE>::new(self.y).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let x = F32::<LE>::deserialize(buf)?.0;
+        let y = F32::<LE>::deserialize(buf)?.0;
+
+        Ok(Self::new(x, y))
+    }
+
+    fn encoded_len(&self) -> usize {
+        8
+    }
+}
+
+impl<'a> Binary<'a> for Vec3 {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        F32::<LE>::new(self.x).serialize(buf)?;
+        F32::<LE>::new(self.y).serialize(buf)?;
+        F32::<LE>::new(self.z).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let x = F32::<LE>::deserialize(buf)?.0;
+        let y = F32::<LE>::deserialize(buf)?.0;
+        let z = F32::<LE>::deserialize(buf)?.0;
+
+        Ok(Self::new(x, y, z))
+    }
+
+    fn encoded_len(&self) -> usize {
+        12
+    }
+}
+
+impl<'a> Binary<'a> for BlockPos {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        VarI32::new(self.x).serialize(buf)?;
+        VarU32::new(self.y as u32).serialize(buf)?;
+        VarI32::new(self.z).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let x = VarI32::deserialize(buf)?.0;
+        let y = VarU32::deserialize(buf)?.0 as i32;
+        let z = VarI32::deserialize(buf)?.0;
+
+        Ok(Self::new(x, y, z))
+    }
+
+    fn encoded_len(&self) -> usize {
+        VarI32::new(self.x).encoded_len()
+            + VarU32::new(self.y as u32).encoded_len()
+            + VarI32::new(self.z).encoded_len()
+    }
+}
+
+impl<'a> Binary<'a> for Uuid {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let (high, low) = self.as_u64_pair();
+
+        U64::<LE>::new(high).serialize(buf)?;
+        U64::<LE>::new(low).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let high = U64::<LE>::deserialize(buf)?.0;
+        let low = U64::<LE>::deserialize(buf)?.0;
+
+        Ok(Self::from_u64_pair(high, low))
+    }
+
+    fn encoded_len(&self) -> usize {
+        16
+    }
+}
+
+impl<'a> Binary<'a> for RotationByte {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        U8::new(self.0).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(Self(U8::deserialize(buf)?.0))
+    }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
+}
+
+///
+/// This macro implements the arithmetic operators for a vector type with the provided fields and
+/// scalar type. Vectors are added to and subtracted from each other, and multiplied and divided by
+/// scalars.
+///
+macro_rules! vector_ops {
+    ($name:ident { $($field:ident),+ }, $scalar:ty) => {
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        impl Mul<$scalar> for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: $scalar) -> Self {
+                Self { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl Div<$scalar> for $name {
+            type Output = Self;
+
+            fn div(self, rhs: $scalar) -> Self {
+                Self { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: Self) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl MulAssign<$scalar> for $name {
+            fn mul_assign(&mut self, rhs: $scalar) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl DivAssign<$scalar> for $name {
+            fn div_assign(&mut self, rhs: $scalar) {
+                *self = *self / rhs;
+            }
+        }
+    };
+}
+
+vector_ops!(Vec2 { x, y }, f32);
+vector_ops!(Vec3 { x, y, z }, f32);
+vector_ops!(BlockPos { x, y, z }, i32);
+
+/// Implements the methods that depend on the length of a float vector.
+macro_rules! vector_len {
+    ($name:ident) => {
+        impl $name {
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> f32 {
+                self.length_squared().sqrt()
+            }
+
+            pub fn distance(self, other: Self) -> f32 {
+                (self - other).length()
+            }
+
+            /// Returns the vector scaled to a length of 1, or the zero vector if its length is zero.
+            pub fn normalize(self) -> Self {
+                let len = self.length();
+
+                if len == 0.0 {
+                    return Self::ZERO;
+                }
+
+                self / len
+            }
+        }
+    };
+}
+
+vector_len!(Vec2);
+vector_len!(Vec3);
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(val: Vec2) -> Self {
+        [val.x, val.y]
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(val: Vec3) -> Self {
+        [val.x, val.y, val.z]
+    }
+}
+
+impl From<(f32, f32, f32)> for Vec3 {
+    fn from((x, y, z): (f32, f32, f32)) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<BlockPos> for Vec3 {
+    /// Returns the position of the lowest corner of the block.
+    fn from(val: BlockPos) -> Self {
+        Self::new(val.x as f32, val.y as f32, val.z as f32)
+    }
+}
+
+impl From<[i32; 3]> for BlockPos {
+    fn from([x, y, z]: [i32; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<BlockPos> for [i32; 3] {
+    fn from(val: BlockPos) -> Self {
+        [val.x, val.y, val.z]
+    }
+}
+
+impl From<(i32, i32, i32)> for BlockPos {
+    fn from((x, y, z): (i32, i32, i32)) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<u128> for Uuid {
+    fn from(val: u128) -> Self {
+        Self(val)
+    }
+}
+
+impl From<Uuid> for u128 {
+    fn from(val: Uuid) -> Self {
+        val.0
+    }
+}
+
+impl From<[u8; 16]> for Uuid {
+    fn from(bytes: [u8; 16]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<Uuid> for [u8; 16] {
+    fn from(val: Uuid) -> Self {
+        val.into_bytes()
+    }
+}
@@ -1,8 +1,12 @@
+use crate::error::{DecodeError, EncodeError};
+use crate::reader::{Reader, VarIntMode};
 use crate::{debug_impl, Binary};
 use byteorder::ByteOrder;
-use byteorder::{ReadBytesExt, WriteBytesExt};
-use std::io::{Cursor, Error, ErrorKind, Result, Write};
+use byteorder::WriteBytesExt;
+use std::borrow::Cow;
+use std::io::Write;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 #[derive(Clone, Default, PartialEq, Eq)]
 #[repr(transparent)]
@@ -53,6 +57,11 @@ pub struct VarI64(pub i64);
 #[derive(Clone, Default, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct VarU64(pub u64);
+/// Strict wraps one of the varint datatypes so that it is always decoded in `VarIntMode::Strict`,
+/// whatever the mode of the reader is.
+#[derive(Clone, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Strict<V>(pub V);
 
 impl Bool {
     pub fn new(val: bool) -> Self {
@@ -136,24 +145,66 @@ impl VarI32 {
     pub fn new(val: i32) -> Self {
         Self(val)
     }
+
+    /// Deserializes the varint in the provided mode instead of the mode of the reader.
+    pub fn deserialize_in<'a>(
+        buf: &mut impl Reader<'a>,
+        mode: VarIntMode,
+    ) -> Result<Self, DecodeError> {
+        let val = read_varint(buf, 32, mode)?;
+        Ok(Self::new(unzigzag32(val as u32)))
+    }
 }
 
 impl VarU32 {
     pub fn new(val: u32) -> Self {
         Self(val)
     }
+
+    /// Deserializes the varint in the provided mode instead of the mode of the reader.
+    pub fn deserialize_in<'a>(
+        buf: &mut impl Reader<'a>,
+        mode: VarIntMode,
+    ) -> Result<Self, DecodeError> {
+        let val = read_varint(buf, 32, mode)?;
+        Ok(Self::new(val as u32))
+    }
 }
 
 impl VarI64 {
     pub fn new(val: i64) -> Self {
         Self(val)
     }
+
+    /// Deserializes the varint in the provided mode instead of the mode of the reader.
+    pub fn deserialize_in<'a>(
+        buf: &mut impl Reader<'a>,
+        mode: VarIntMode,
+    ) -> Result<Self, DecodeError> {
+        let val = read_varint(buf, 64, mode)?;
+        Ok(Self::new(unzigzag64(val)))
+    }
 }
 
 impl VarU64 {
     pub fn new(val: u64) -> Self {
         Self(val)
     }
+
+    /// Deserializes the varint in the provided mode instead of the mode of the reader.
+    pub fn deserialize_in<'a>(
+        buf: &mut impl Reader<'a>,
+        mode: VarIntMode,
+    ) -> Result<Self, DecodeError> {
+        let val = read_varint(buf, 64, mode)?;
+        Ok(Self::new(val))
+    }
+}
+
+impl<V> Strict<V> {
+    pub fn new(val: V) -> Self {
+        Self(val)
+    }
 }
 
 debug_impl!(U8);
@@ -173,314 +224,663 @@ debug_impl!(VarU32);
 debug_impl!(VarI32);
 debug_impl!(VarU64);
 debug_impl!(VarI64);
+debug_impl!(Strict<V: std::fmt::Debug>);
 
 impl<'a> Binary<'a> for Bool {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_u8(self.0 as u8).unwrap()
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_u8(self.0 as u8)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
         let n = buf.read_u8()?;
         Ok(Self::new(n == 1))
     }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
 }
 
 impl<'a> Binary<'a> for U8 {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_u8(self.0).unwrap()
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_u8(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
         let n = buf.read_u8()?;
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
 }
 
 impl<'a> Binary<'a> for I8 {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_i8(self.0).unwrap()
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_i8(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_i8()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = buf.read_u8()? as i8;
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for U16<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_u16::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_u16::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_u16::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_u16(&buf.read_array::<2>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        2
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for I16<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_i16::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_i16::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_i16::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_i16(&buf.read_array::<2>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        2
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for U24<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_u24::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_u24::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_u24::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_u24(&buf.read_array::<3>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        3
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for I24<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_i24::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_i24::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_i24::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_i24(&buf.read_array::<3>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        3
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for U32<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_u32::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_u32::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_u32::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_u32(&buf.read_array::<4>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        4
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for I32<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_i32::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_i32::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_i32::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_i32(&buf.read_array::<4>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        4
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for U64<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_u64::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_u64::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_u64::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_u64(&buf.read_array::<8>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        8
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for I64<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_i64::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_i64::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_i64::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_i64(&buf.read_array::<8>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        8
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for F32<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_f32::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_f32::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_f32::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_f32(&buf.read_array::<4>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        4
+    }
 }
 
 impl<'a, E: ByteOrder> Binary<'a> for F64<E> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_f64::<E>(self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_f64::<E>(self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let n = buf.read_f64::<E>()?;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let n = E::read_f64(&buf.read_array::<8>()?);
         Ok(Self::new(n))
     }
+
+    fn encoded_len(&self) -> usize {
+        8
+    }
 }
 
 impl<'a> Binary<'a> for VarI32 {
-    fn serialize(&self, buf: &mut impl Write) {
-        let u = self.0;
-        let mut ux = (self.0 as u32) << 1;
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        write_varint(zigzag32(self.0) as u64, buf)
+    }
 
-        if u < 0 {
-            ux = !ux;
-        }
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let mode = buf.varint_mode();
+        Self::deserialize_in(buf, mode)
+    }
 
-        while ux >= 0x80 {
-            U8::new(ux as u8 | 0x80).serialize(buf);
-            ux >>= 7;
-        }
+    fn encoded_len(&self) -> usize {
+        varint_len(zigzag32(self.0) as u64)
+    }
+}
+
+impl<'a> Binary<'a> for VarU32 {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        write_varint(self.0 as u64, buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let mode = buf.varint_mode();
+        Self::deserialize_in(buf, mode)
+    }
+
+    fn encoded_len(&self) -> usize {
+        varint_len(self.0 as u64)
+    }
+}
+
+impl<'a> Binary<'a> for VarI64 {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        write_varint(zigzag64(self.0), buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let mode = buf.varint_mode();
+        Self::deserialize_in(buf, mode)
+    }
+
+    fn encoded_len(&self) -> usize {
+        varint_len(zigzag64(self.0))
+    }
+}
+
+impl<'a> Binary<'a> for VarU64 {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        write_varint(self.0, buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let mode = buf.varint_mode();
+        Self::deserialize_in(buf, mode)
+    }
 
-        U8::new(ux as u8).serialize(buf);
+    fn encoded_len(&self) -> usize {
+        varint_len(self.0)
     }
+}
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let mut ux = 0;
+///
+/// This macro implements `Binary` for the strict version of a varint datatype, which is encoded the same
+/// way but always decoded in `VarIntMode::Strict`.
+///
+macro_rules! strict_impl {
+    ($datatype:ident) => {
+        impl<'a> Binary<'a> for Strict<$datatype> {
+            fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+                self.0.serialize(buf)
+            }
 
-        for i in (0..35).step_by(7) {
-            let b = U8::deserialize(buf)?.0;
-            ux |= ((b & 0x7f) as i32) << i;
+            fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+                Ok(Self($datatype::deserialize_in(buf, VarIntMode::Strict)?))
+            }
 
-            if b & 0x80 == 0 {
-                let mut x = (ux as i32) >> 1;
-                if ux & 1 != 0 {
-                    x = !x;
-                }
+            fn encoded_len(&self) -> usize {
+                self.0.encoded_len()
+            }
+        }
+    };
+}
 
-                return Ok(VarI32::new(x));
+strict_impl!(VarI32);
+strict_impl!(VarU32);
+strict_impl!(VarI64);
+strict_impl!(VarU64);
+
+impl<'a, B: Binary<'a>> Binary<'a> for Option<B> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        match self {
+            Some(val) => {
+                Bool::new(true).serialize(buf)?;
+                val.serialize(buf)
             }
+            None => Bool::new(false).serialize(buf),
         }
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let bool = Bool::deserialize(buf)?.0;
 
-        Err(Error::new(
-            ErrorKind::Other,
-            "VarI32 size must not exceed 5 bytes",
-        ))
+        match bool {
+            true => {
+                let val = B::deserialize(buf)?;
+                Ok(Some(val))
+            }
+            false => Ok(None),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            Some(val) => 1 + val.encoded_len(),
+            None => 1,
+        }
     }
 }
 
-impl<'a> Binary<'a> for VarU32 {
-    fn serialize(&self, buf: &mut impl Write) {
-        let mut u = self.0;
+/// Fixed length arrays are encoded as their elements one after another, without a length prefix.
+impl<'a, B: Binary<'a>, const N: usize> Binary<'a> for [B; N] {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        for val in self {
+            val.serialize(buf)?;
+        }
 
-        while u >= 0x80 {
-            U8::new(u as u8 | 0x80).serialize(buf);
-            u >>= 7;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let mut vals = Vec::with_capacity(N);
+
+        for _ in 0..N {
+            vals.push(B::deserialize(buf)?);
         }
 
-        U8::new(u as u8).serialize(buf);
+        Ok(vals
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("exactly {} elements were decoded", N)))
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let mut v = 0;
+    fn encoded_len(&self) -> usize {
+        self.iter().map(|val| val.encoded_len()).sum()
+    }
+}
+
+///
+/// This macro implements `Binary` for a tuple of the provided types, which are encoded one after another
+/// in order.
+///
+macro_rules! tuple_impl {
+    ($($name:ident),+) => {
+        impl<'a, $($name: Binary<'a>),+> Binary<'a> for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+                let ($($name,)+) = self;
+                $($name.serialize(buf)?;)+
+                Ok(())
+            }
 
-        for i in (0..35).step_by(7) {
-            let b = U8::deserialize(buf)?.0;
-            v |= ((b & 0x7f) as u32) << (i as u32);
+            fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+                Ok(($($name::deserialize(buf)?,)+))
+            }
 
-            if b & 0x80 == 0 {
-                return Ok(VarU32::new(v));
+            #[allow(non_snake_case)]
+            fn encoded_len(&self) -> usize {
+                let ($($name,)+) = self;
+                0 $(+ $name.encoded_len())+
             }
         }
+    };
+}
+
+tuple_impl!(A);
+tuple_impl!(A, B);
+tuple_impl!(A, B, C);
+tuple_impl!(A, B, C, D);
+tuple_impl!(A, B, C, D, E);
+tuple_impl!(A, B, C, D, E, F);
+tuple_impl!(A, B, C, D, E, F, G);
+tuple_impl!(A, B, C, D, E, F, G, H);
+tuple_impl!(A, B, C, D, E, F, G, H, I);
+tuple_impl!(A, B, C, D, E, F, G, H, I, J);
+tuple_impl!(A, B, C, D, E, F, G, H, I, J, K);
+tuple_impl!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+impl<'a, B: Binary<'a>> Binary<'a> for Box<B> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        self.as_ref().serialize(buf)
+    }
 
-        Err(Error::new(
-            ErrorKind::Other,
-            "VarU32 size must not exceed 5 bytes",
-        ))
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(Box::new(B::deserialize(buf)?))
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.as_ref().encoded_len()
     }
 }
 
-impl<'a> Binary<'a> for VarI64 {
-    fn serialize(&self, buf: &mut impl Write) {
-        let u = self.0;
-        let mut ux = (self.0 as u64) << 1;
+impl<'a, B: Binary<'a>> Binary<'a> for Arc<B> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        self.as_ref().serialize(buf)
+    }
 
-        if u < 0 {
-            ux = !ux;
-        }
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(Arc::new(B::deserialize(buf)?))
+    }
 
-        while ux >= 0x80 {
-            U8::new(ux as u8 | 0x80).serialize(buf);
-            ux >>= 7;
-        }
+    fn encoded_len(&self) -> usize {
+        self.as_ref().encoded_len()
+    }
+}
+
+/// A `Cow` is encoded as the value it points to, and is always decoded into `Cow::Owned`.
+impl<'a, 'b, B: Binary<'a> + Clone> Binary<'a> for Cow<'b, B> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        self.as_ref().serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(Cow::Owned(B::deserialize(buf)?))
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.as_ref().encoded_len()
+    }
+}
 
-        U8::new(ux as u8).serialize(buf);
+/// `PhantomData` takes no bytes at all, which makes it useful for markers in generic structs.
+impl<'a, T: ?Sized> Binary<'a> for PhantomData<T> {
+    fn serialize(&self, _: &mut impl Write) -> Result<(), EncodeError> {
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let mut ux = 0;
+    fn deserialize(_: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(PhantomData)
+    }
+
+    fn encoded_len(&self) -> usize {
+        0
+    }
+}
 
-        for i in (0..70).step_by(7) {
-            let b = U8::deserialize(buf)?.0;
-            ux |= ((b & 0x7f) as i64) << (i as i64);
+///
+/// This macro implements `Binary` for the primitives that have only one possible encoding by delegating
+/// to their datatype. Wider primitives select their encoding with `encoding::Encoding` instead.
+///
+macro_rules! primitive_impl {
+    ($ty:ty => $datatype:ident) => {
+        impl<'a> Binary<'a> for $ty {
+            fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+                $datatype::new(*self).serialize(buf)
+            }
 
-            if b & 0x80 == 0 {
-                let mut x = (ux >> 1) as i64;
-                if ux & 1 != 0 {
-                    x = !x;
-                }
+            fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+                Ok($datatype::deserialize(buf)?.0)
+            }
 
-                return Ok(VarI64::new(x));
+            fn encoded_len(&self) -> usize {
+                1
             }
         }
+    };
+}
+
+primitive_impl!(bool => Bool);
+primitive_impl!(u8 => U8);
+primitive_impl!(i8 => I8);
 
-        Err(Error::new(
-            ErrorKind::Other,
-            "VarI64 size must not exceed 10 bytes",
-        ))
+/// Writes the value as a varint with a single write. Single byte values are by far the most common, so
+/// they are kept apart from the rest to be inlined into the callers.
+#[inline]
+fn write_varint(val: u64, buf: &mut impl Write) -> Result<(), EncodeError> {
+    if val < 0x80 {
+        buf.write_all(&[val as u8])?;
+        return Ok(());
     }
+
+    write_varint_long(val, buf)
 }
 
-impl<'a> Binary<'a> for VarU64 {
-    fn serialize(&self, buf: &mut impl Write) {
-        let mut u = self.0;
+/// Writes a varint of at least two bytes. Values that fit into 8 bytes are encoded by spreading their
+/// 7 bit groups over a word, and larger ones byte by byte into a stack buffer.
+#[inline]
+fn write_varint_long(val: u64, buf: &mut impl Write) -> Result<(), EncodeError> {
+    if val >= 1 << 56 {
+        let mut data = [0; 10];
+        let mut len = 0;
+        let mut val = val;
 
-        while u >= 0x80 {
-            U8::new(u as u8 | 0x80).serialize(buf);
-            u >>= 7;
+        while val >= 0x80 {
+            data[len] = val as u8 | 0x80;
+            val >>= 7;
+            len += 1;
         }
 
-        U8::new(u as u8).serialize(buf);
+        data[len] = val as u8;
+        buf.write_all(&data[..=len])?;
+        return Ok(());
+    }
+
+    let len = varint_len(val);
+    let mut word = 0;
+
+    for i in 0..8 {
+        word |= ((val >> (i * 7)) & 0x7f) << (i * 8);
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let mut v = 0;
+    // Every byte but the last has the continuation bit set.
+    word |= 0x8080_8080_8080_8080 & (u64::MAX >> (64 - (len - 1) * 8));
+    let data = word.to_le_bytes();
+
+    // Writing slices of a constant length lets the copy be inlined for writers such as `Vec`.
+    match len {
+        2 => buf.write_all(&data[..2])?,
+        3 => buf.write_all(&data[..3])?,
+        4 => buf.write_all(&data[..4])?,
+        5 => buf.write_all(&data[..5])?,
+        6 => buf.write_all(&data[..6])?,
+        7 => buf.write_all(&data[..7])?,
+        _ => buf.write_all(&data)?,
+    }
 
-        for i in (0..70).step_by(7) {
-            let b = U8::deserialize(buf)?.0;
-            v |= ((b & 0x7f) as u64) << (i as u64);
+    Ok(())
+}
 
-            if b & 0x80 == 0 {
-                return Ok(VarU64::new(v));
-            }
+/// Reads a varint of a type that is `bits` wide. In `VarIntMode::Lenient` the bits of the last byte that
+/// do not fit into the type are dropped and over-long encodings are accepted, while in
+/// `VarIntMode::Strict` both are rejected so that every value has exactly one accepted encoding.
+fn read_varint<'a>(
+    buf: &mut impl Reader<'a>,
+    bits: u32,
+    mode: VarIntMode,
+) -> Result<u64, DecodeError> {
+    // Most varints are a single byte, which is always canonical and always fits.
+    if let Some(&b) = buf.chunk().first() {
+        if b < 0x80 {
+            buf.advance(1)?;
+            return Ok(b as u64);
+        }
+    }
+
+    let offset = buf.position();
+    let max_bytes = bits.div_ceil(7);
+
+    let (val, len, last) = match decode_varint_word(buf.chunk()) {
+        Some((val, len, last)) if len <= max_bytes => {
+            buf.advance(len as usize)?;
+            (val, len, last)
+        }
+        _ => read_varint_bytes(buf, offset, max_bytes)?,
+    };
+
+    if mode == VarIntMode::Strict {
+        if len == max_bytes && last >> (bits - (len - 1) * 7) != 0 {
+            return Err(DecodeError::VarIntOverflow {
+                offset,
+                max_bytes: max_bytes as usize,
+            });
         }
 
-        Err(Error::new(
-            ErrorKind::Other,
-            "VarU64 size must not exceed 10 bytes",
-        ))
+        // A last byte of zero adds nothing to the value, so a shorter encoding exists.
+        if len > 1 && last == 0 {
+            return Err(DecodeError::NonCanonicalVarInt { offset });
+        }
     }
+
+    Ok(val)
 }
 
-impl<'a, B: Binary<'a>> Binary<'a> for Option<B> {
-    fn serialize(&self, buf: &mut impl Write) {
-        match self {
-            Some(val) => {
-                Bool::new(true).serialize(buf);
-                val.serialize(buf);
-            }
-            None => Bool::new(false).serialize(buf),
-        }
+/// Decodes a varint of up to 8 bytes from the first 8 bytes of the slice without branching on every
+/// byte. Returns the value, the length of the encoding and its last byte, or `None` if the slice is too
+/// short or the varint does not end within 8 bytes.
+#[inline]
+fn decode_varint_word(data: &[u8]) -> Option<(u64, u32, u8)> {
+    let word = u64::from_le_bytes(data.get(..8)?.try_into().ok()?);
+
+    // Every byte without the continuation bit ends the varint, the first one of them is the last byte.
+    let ends = !word & 0x8080_8080_8080_8080;
+    if ends == 0 {
+        return None;
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let bool = Bool::deserialize(buf)?.0;
+    let len = ends.trailing_zeros() / 8 + 1;
+    let word = word & (u64::MAX >> (64 - len * 8));
+    let last = (word >> ((len - 1) * 8)) as u8;
 
-        match bool {
-            true => {
-                let val = B::deserialize(buf)?;
-                Ok(Some(val))
-            }
-            false => Ok(None),
+    // Gather the 7 bit groups of all the bytes next to each other.
+    let mut val = 0;
+    for i in 0..8 {
+        val |= (word >> i) & (0x7f << (i * 7));
+    }
+
+    Some((val, len, last))
+}
+
+/// Reads a varint one byte at a time, for buffers that do not have enough contiguous bytes for
+/// `decode_varint_word`. Returns the same as `decode_varint_word`.
+fn read_varint_bytes<'a>(
+    buf: &mut impl Reader<'a>,
+    offset: usize,
+    max_bytes: u32,
+) -> Result<(u64, u32, u8), DecodeError> {
+    let mut val = 0;
+
+    for i in 0..max_bytes {
+        let b = buf.read_u8()?;
+        val |= ((b & 0x7f) as u64) << (i * 7);
+
+        if b & 0x80 == 0 {
+            return Ok((val, i + 1, b));
         }
     }
+
+    Err(DecodeError::VarIntOverflow {
+        offset,
+        max_bytes: max_bytes as usize,
+    })
+}
+
+/// Returns the amount of bytes that the unsigned value takes when encoded as a varint.
+#[inline]
+pub(crate) fn varint_len(val: u64) -> usize {
+    (64 - (val | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+/// Maps a signed 32-bit value to an unsigned one using zigzag encoding, so that values with a small
+/// magnitude take only a few bytes.
+#[inline]
+pub(crate) fn zigzag32(val: i32) -> u32 {
+    ((val << 1) ^ (val >> 31)) as u32
+}
+
+/// Maps a signed 64-bit value to an unsigned one using zigzag encoding.
+#[inline]
+pub(crate) fn zigzag64(val: i64) -> u64 {
+    ((val << 1) ^ (val >> 63)) as u64
+}
+
+/// Maps a zigzag encoded 32-bit value back to the signed value.
+#[inline]
+pub(crate) fn unzigzag32(val: u32) -> i32 {
+    ((val >> 1) as i32) ^ -((val & 1) as i32)
+}
+
+/// Maps a zigzag encoded 64-bit value back to the signed value.
+#[inline]
+pub(crate) fn unzigzag64(val: u64) -> i64 {
+    ((val >> 1) as i64) ^ -((val & 1) as i64)
 }
@@ -0,0 +1,297 @@
+use crate::datatypes::{
+    Strict, VarI32, VarI64, VarU32, VarU64, F32, F64, I16, I24, I32, I64, I8, U16, U24, U32, U64,
+    U8,
+};
+use crate::error::{DecodeError, EncodeError};
+use crate::prefixed::{OwnedArray, OwnedStr, Prefix, Str};
+use crate::reader::Reader;
+use crate::Binary;
+use byteorder::ByteOrder;
+use std::io::Write;
+use std::marker::PhantomData;
+
+pub use byteorder::{BE, LE};
+
+///
+/// Encoding is implemented by marker types that select how a plain Rust type is written, so that structs
+/// can use `u16`, `i32` or `String` fields instead of the wrapper datatypes. It is what the
+/// `#[binary(le)]`, `#[binary(be)]`, `#[binary(varint)]` and `#[binary(prefix = "...")]` field attributes
+/// of the derive macro expand to.
+///
+/// - `LE` and `BE` encode the fixed width integers and floats in the byte order.
+/// - `VarInt` encodes the 32-bit and 64-bit integers as varints.
+/// - Any `Prefix` type encodes `String` and `Vec<B>` with the length prefix.
+/// - `Prefixed<P, E>` encodes `Vec<T>` with the length prefix `P` and every element with `E`.
+///
+/// All of them delegate to the datatype that implements the same encoding.
+///
+pub trait Encoding<'a, T> {
+    fn serialize(val: &T, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<T, DecodeError>;
+    fn encoded_len(val: &T) -> usize;
+}
+
+/// Marker for encoding integers as varints, the signed ones being zigzag encoded.
+pub struct VarInt;
+
+/// Marker for encoding a `Vec` with the length prefix `P` and every element with the encoding `E`.
+pub struct Prefixed<P: Prefix, E>(PhantomData<(P, E)>);
+
+///
+/// This macro implements `Encoding` for a primitive by wrapping it into the datatype that implements
+/// the same encoding. For the fixed width datatypes the marker is the byte order itself.
+///
+macro_rules! encoding_impl {
+    ($ty:ty => $datatype:ident<E>) => {
+        impl<'a, E: ByteOrder> Encoding<'a, $ty> for E {
+            fn serialize(val: &$ty, buf: &mut impl Write) -> Result<(), EncodeError> {
+                $datatype::<E>::new(*val).serialize(buf)
+            }
+
+            fn deserialize(buf: &mut impl Reader<'a>) -> Result<$ty, DecodeError> {
+                Ok(<$datatype<E> as Binary>::deserialize(buf)?.0)
+            }
+
+            fn encoded_len(val: &$ty) -> usize {
+                $datatype::<E>::new(*val).encoded_len()
+            }
+        }
+    };
+    ($ty:ty => $marker:ident, $datatype:ident) => {
+        impl<'a> Encoding<'a, $ty> for $marker {
+            fn serialize(val: &$ty, buf: &mut impl Write) -> Result<(), EncodeError> {
+                $datatype::new(*val).serialize(buf)
+            }
+
+            fn deserialize(buf: &mut impl Reader<'a>) -> Result<$ty, DecodeError> {
+                Ok(<$datatype as Binary>::deserialize(buf)?.0)
+            }
+
+            fn encoded_len(val: &$ty) -> usize {
+                $datatype::new(*val).encoded_len()
+            }
+        }
+    };
+}
+
+encoding_impl!(u16 => U16<E>);
+encoding_impl!(i16 => I16<E>);
+encoding_impl!(u32 => U32<E>);
+encoding_impl!(i32 => I32<E>);
+encoding_impl!(u64 => U64<E>);
+encoding_impl!(i64 => I64<E>);
+encoding_impl!(f32 => F32<E>);
+encoding_impl!(f64 => F64<E>);
+encoding_impl!(u32 => VarInt, VarU32);
+encoding_impl!(i32 => VarInt, VarI32);
+encoding_impl!(u64 => VarInt, VarU64);
+encoding_impl!(i64 => VarInt, VarI64);
+
+impl<'a, P: Prefix> Encoding<'a, String> for P {
+    fn serialize(val: &String, buf: &mut impl Write) -> Result<(), EncodeError> {
+        Str::<P>::new(val).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<String, DecodeError> {
+        Ok(OwnedStr::<P>::deserialize(buf)?.into_inner())
+    }
+
+    fn encoded_len(val: &String) -> usize {
+        Str::<P>::new(val).encoded_len()
+    }
+}
+
+impl<'a, B: Binary<'a>, P: Prefix> Encoding<'a, Vec<B>> for P {
+    fn serialize(val: &Vec<B>, buf: &mut impl Write) -> Result<(), EncodeError> {
+        P::encode(val.len(), buf)?;
+
+        for element in val {
+            element.serialize(buf)?;
+        }
+
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Vec<B>, DecodeError> {
+        Ok(OwnedArray::<B, P>::deserialize(buf)?.into_inner())
+    }
+
+    fn encoded_len(val: &Vec<B>) -> usize {
+        P::encoded_len(val.len()) + val.iter().map(B::encoded_len).sum::<usize>()
+    }
+}
+
+impl<'a, T, P: Prefix, E: Encoding<'a, T>> Encoding<'a, Vec<T>> for Prefixed<P, E> {
+    fn serialize(val: &Vec<T>, buf: &mut impl Write) -> Result<(), EncodeError> {
+        P::encode(val.len(), buf)?;
+
+        for element in val {
+            E::serialize(element, buf)?;
+        }
+
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Vec<T>, DecodeError> {
+        let offset = buf.position();
+        let len = P::decode(buf)?;
+        buf.check_elements(offset, len)?;
+
+        buf.nested(|buf| {
+            let mut array = Vec::with_capacity(len.min(buf.remaining()));
+
+            for i in 0..len {
+                array.push(E::deserialize(buf).map_err(|err| err.in_element(i))?);
+            }
+
+            Ok(array)
+        })
+    }
+
+    fn encoded_len(val: &Vec<T>) -> usize {
+        P::encoded_len(val.len()) + val.iter().map(E::encoded_len).sum::<usize>()
+    }
+}
+
+///
+/// Count is implemented by the integers and datatypes that can hold the amount of elements of an array
+/// or the length in bytes of a field encoded in another field, which the `#[binary(count = "...")]` and
+/// `#[binary(len_bytes = "...")]` field attributes of the derive macro read when decoding and write when
+/// encoding.
+///
+pub trait Count: Sized {
+    /// The largest count that can be held.
+    const MAX: usize;
+
+    /// Returns the count, or `None` if it is negative.
+    fn to_count(&self) -> Option<usize>;
+    /// Creates the value holding the count, or returns `None` if it is over `MAX`.
+    fn from_count(count: usize) -> Option<Self>;
+}
+
+///
+/// This macro implements `Count` for the primitives and for the datatypes by delegating to the primitive
+/// that they wrap.
+///
+macro_rules! count_impl {
+    ($($ty:ty),+) => {
+        $(
+            impl Count for $ty {
+                const MAX: usize = <$ty>::MAX as usize;
+
+                fn to_count(&self) -> Option<usize> {
+                    usize::try_from(*self).ok()
+                }
+
+                fn from_count(count: usize) -> Option<Self> {
+                    <$ty>::try_from(count).ok()
+                }
+            }
+        )+
+    };
+    ($($datatype:ident$(<$e:ident>)? => $ty:ty, $max:expr);+ $(;)?) => {
+        $(
+            impl$(<$e: ByteOrder>)? Count for $datatype$(<$e>)? {
+                const MAX: usize = $max;
+
+                fn to_count(&self) -> Option<usize> {
+                    self.0.to_count()
+                }
+
+                fn from_count(count: usize) -> Option<Self> {
+                    match count <= <Self as Count>::MAX {
+                        true => Some(Self::new(<$ty>::from_count(count)?)),
+                        false => None,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+count_impl!(u8, u16, u32, u64, usize, i8, i16, i32, i64);
+count_impl!(
+    U8 => u8, u8::MAX as usize;
+    I8 => i8, i8::MAX as usize;
+    U16<E> => u16, u16::MAX as usize;
+    I16<E> => i16, i16::MAX as usize;
+    U24<E> => u32, 0xFF_FFFF;
+    I24<E> => i32, 0x7F_FFFF;
+    U32<E> => u32, u32::MAX as usize;
+    I32<E> => i32, i32::MAX as usize;
+    U64<E> => u64, u64::MAX as usize;
+    I64<E> => i64, i64::MAX as usize;
+    VarU32 => u32, u32::MAX as usize;
+    VarI32 => i32, i32::MAX as usize;
+    VarU64 => u64, u64::MAX as usize;
+    VarI64 => i64, i64::MAX as usize;
+);
+
+///
+/// Discriminant is implemented by the datatypes that can encode the tag of an enum, which the
+/// `#[binary(tag = ...)]` attribute of the derive macro selects. Discriminants are converted through `i128`
+/// so that both negative and 64-bit tags keep their value.
+///
+pub trait Discriminant: Sized {
+    /// The smallest discriminant that the tag can hold. The derive macro checks the discriminants of the
+    /// variants against `MIN` and `MAX` at compile time.
+    const MIN: i128;
+    /// The largest discriminant that the tag can hold.
+    const MAX: i128;
+
+    /// Creates the tag holding the discriminant, or returns `None` if it does not fit.
+    fn from_discriminant(disc: i128) -> Option<Self>;
+    /// Returns the discriminant held by the tag.
+    fn to_discriminant(&self) -> i128;
+}
+
+///
+/// This macro implements `Discriminant` for the datatypes by converting to and from the primitive that
+/// they wrap.
+///
+macro_rules! discriminant_impl {
+    ($($datatype:ident$(<$e:ident>)? => $ty:ty),+ $(,)?) => {
+        $(
+            impl$(<$e: ByteOrder>)? Discriminant for $datatype$(<$e>)? {
+                const MIN: i128 = <$ty>::MIN as i128;
+                const MAX: i128 = <$ty>::MAX as i128;
+
+                fn from_discriminant(disc: i128) -> Option<Self> {
+                    <$ty>::try_from(disc).ok().map(Self::new)
+                }
+
+                fn to_discriminant(&self) -> i128 {
+                    self.0 as i128
+                }
+            }
+        )+
+    };
+}
+
+discriminant_impl!(
+    U8 => u8,
+    I8 => i8,
+    U16<E> => u16,
+    I16<E> => i16,
+    U32<E> => u32,
+    I32<E> => i32,
+    U64<E> => u64,
+    I64<E> => i64,
+    VarU32 => u32,
+    VarI32 => i32,
+    VarU64 => u64,
+    VarI64 => i64,
+);
+
+impl<V: Discriminant> Discriminant for Strict<V> {
+    const MIN: i128 = V::MIN;
+    const MAX: i128 = V::MAX;
+
+    fn from_discriminant(disc: i128) -> Option<Self> {
+        V::from_discriminant(disc).map(Self::new)
+    }
+
+    fn to_discriminant(&self) -> i128 {
+        self.0.to_discriminant()
+    }
+}
@@ -0,0 +1,268 @@
+use crate::nbt::TagError;
+use crate::reader::Reader;
+use std::fmt::{Display, Formatter};
+use std::str::Utf8Error;
+
+/// DecodeError is returned by `Binary::deserialize` when the data coming from the other end is not
+/// in the format we expect. Every variant records the byte offset in the buffer at which decoding failed,
+/// so that packet handlers can match on what went wrong and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The buffer ended before the value could be read completely.
+    UnexpectedEof { offset: usize },
+    /// A variable length integer did not fit into its type, either because it did not terminate within
+    /// the maximum amount of bytes for the type or, when decoding strictly, because its last byte had
+    /// bits set beyond the width of the type.
+    VarIntOverflow { offset: usize, max_bytes: usize },
+    /// A variable length integer was encoded with more bytes than needed while decoding strictly.
+    NonCanonicalVarInt { offset: usize },
+    /// A string did not contain valid UTF-8.
+    InvalidUtf8 { offset: usize, error: Utf8Error },
+    /// An enum discriminant did not match any of the variants of the enum.
+    InvalidDiscriminant { offset: usize, value: i128 },
+    /// A length read from the buffer was over the limit allowed for it.
+    LengthLimit {
+        offset: usize,
+        len: usize,
+        limit: usize,
+    },
+    /// A signed length prefix contained a negative value.
+    NegativeLength { offset: usize, len: i64 },
+    /// Values were nested deeper than the limit allowed.
+    DepthLimit { offset: usize, limit: usize },
+    /// The value was decoded completely but there were bytes left over in the buffer.
+    TrailingBytes { offset: usize, remaining: usize },
+    /// Any other error with a custom message.
+    Custom { offset: usize, message: String },
+    /// An NBT tag decoded at the offset did not have the shape of the type it was read into.
+    Nbt { offset: usize, error: TagError },
+    /// The error occurred while decoding a field of a type deriving `Binary` or an element of an array.
+    /// The path leads from the outermost type, if it is known, to the value that failed to decode. It is
+    /// displayed together with the wrapped error, which is therefore not its `source`, and `root`
+    /// returns it instead.
+    InField {
+        ty: Option<&'static str>,
+        path: Vec<PathSegment>,
+        error: Box<DecodeError>,
+    },
+}
+
+/// A step of the path in `DecodeError::InField`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The variant of an enum, displayed as `::Variant`.
+    Variant(&'static str),
+    /// A field of a struct or variant, displayed as `.field`. Tuple fields are named by their index.
+    Field(&'static str),
+    /// An element of an array, displayed as `[index]`.
+    Index(usize),
+}
+
+impl DecodeError {
+    /// Creates an `UnexpectedEof` error at the current position of the buffer.
+    pub fn eof<'a>(buf: &impl Reader<'a>) -> Self {
+        Self::UnexpectedEof {
+            offset: buf.position(),
+        }
+    }
+
+    /// Creates a `Custom` error at the current position of the buffer.
+    pub fn custom<'a>(buf: &impl Reader<'a>, message: impl Into<String>) -> Self {
+        Self::Custom {
+            offset: buf.position(),
+            message: message.into(),
+        }
+    }
+
+    /// Wraps the error into the field of the type it occurred in, which is added to the front of the path.
+    pub fn in_field(self, ty: &'static str, field: &'static str) -> Self {
+        self.in_path(Some(ty), &[PathSegment::Field(field)])
+    }
+
+    /// Wraps the error into the field of the enum variant it occurred in.
+    pub fn in_variant(self, ty: &'static str, variant: &'static str, field: &'static str) -> Self {
+        self.in_path(
+            Some(ty),
+            &[PathSegment::Variant(variant), PathSegment::Field(field)],
+        )
+    }
+
+    /// Wraps the error into the element of an array it occurred in. The type is left unknown until the
+    /// array is wrapped into the field it is decoded into.
+    pub fn in_element(self, index: usize) -> Self {
+        self.in_path(None, &[PathSegment::Index(index)])
+    }
+
+    /// Adds the segments to the front of the path, replacing the type that the path starts from.
+    fn in_path(self, ty: Option<&'static str>, segments: &[PathSegment]) -> Self {
+        let (path, error) = match self {
+            Self::InField { path, error, .. } => {
+                (segments.iter().cloned().chain(path).collect(), error)
+            }
+            error => (segments.to_vec(), Box::new(error)),
+        };
+
+        Self::InField { ty, path, error }
+    }
+
+    /// Returns the error without the path to the value that it occurred in.
+    pub fn root(&self) -> &DecodeError {
+        match self {
+            Self::InField { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Returns the byte offset in the buffer at which the error occurred.
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::UnexpectedEof { offset }
+            | Self::VarIntOverflow { offset, .. }
+            | Self::NonCanonicalVarInt { offset }
+            | Self::InvalidUtf8 { offset, .. }
+            | Self::InvalidDiscriminant { offset, .. }
+            | Self::LengthLimit { offset, .. }
+            | Self::NegativeLength { offset, .. }
+            | Self::DepthLimit { offset, .. }
+            | Self::TrailingBytes { offset, .. }
+            | Self::Custom { offset, .. }
+            | Self::Nbt { offset, .. } => *offset,
+            Self::InField { error, .. } => error.offset(),
+        }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof { offset } => write!(f, "unexpected EOF at byte {}", offset),
+            Self::VarIntOverflow { offset, max_bytes } => {
+                write!(
+                    f,
+                    "varint does not fit into {} bytes at byte {}",
+                    max_bytes, offset
+                )
+            }
+            Self::NonCanonicalVarInt { offset } => {
+                write!(f, "non-canonical varint at byte {}", offset)
+            }
+            Self::InvalidUtf8 { offset, error } => {
+                write!(f, "invalid UTF-8 at byte {}: {}", offset, error)
+            }
+            Self::InvalidDiscriminant { offset, value } => {
+                write!(
+                    f,
+                    "unexpected enum discriminant {} at byte {}",
+                    value, offset
+                )
+            }
+            Self::LengthLimit { offset, len, limit } => write!(
+                f,
+                "length {} exceeds the limit of {} at byte {}",
+                len, limit, offset
+            ),
+            Self::NegativeLength { offset, len } => {
+                write!(f, "negative length {} at byte {}", len, offset)
+            }
+            Self::DepthLimit { offset, limit } => {
+                write!(
+                    f,
+                    "nesting exceeds the depth of {} at byte {}",
+                    limit, offset
+                )
+            }
+            Self::TrailingBytes { offset, remaining } => {
+                write!(f, "{} trailing bytes at byte {}", remaining, offset)
+            }
+            Self::Custom { offset, message } => write!(f, "{} at byte {}", message, offset),
+            Self::Nbt { offset, error } => write!(f, "{} in NBT at byte {}", error, offset),
+            Self::InField { ty, path, error } => {
+                if let Some(ty) = ty {
+                    write!(f, "{}", ty)?;
+                }
+
+                for segment in path {
+                    match segment {
+                        PathSegment::Variant(variant) => write!(f, "::{}", variant)?,
+                        PathSegment::Field(field) => write!(f, ".{}", field)?,
+                        PathSegment::Index(index) => write!(f, "[{}]", index)?,
+                    }
+                }
+
+                write!(f, ": {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidUtf8 { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for std::io::Error {
+    fn from(err: DecodeError) -> Self {
+        let kind = match err.root() {
+            DecodeError::UnexpectedEof { .. } => std::io::ErrorKind::UnexpectedEof,
+            _ => std::io::ErrorKind::InvalidData,
+        };
+
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// EncodeError is returned by `Binary::serialize` when a value cannot be written to the buffer, either
+/// because the underlying writer failed or because the value cannot be represented in its encoding.
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The underlying writer returned an error, for example because a fixed size buffer was full.
+    Io(std::io::Error),
+    /// A length did not fit into the prefix type used to encode it.
+    LengthOverflow { len: usize, max: usize },
+    /// Any other error with a custom message.
+    Custom(String),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::LengthOverflow { len, max } => {
+                write!(
+                    f,
+                    "length {} does not fit into a prefix of at most {}",
+                    len, max
+                )
+            }
+            Self::Custom(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EncodeError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<EncodeError> for std::io::Error {
+    fn from(err: EncodeError) -> Self {
+        match err {
+            EncodeError::Io(err) => err,
+            err => std::io::Error::new(std::io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
@@ -1,24 +1,74 @@
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod composite;
 pub mod datatypes;
+pub mod encoding;
+pub mod error;
+pub mod nbt;
+pub mod packet;
 pub mod prefixed;
+pub mod reader;
 
-use std::{
-    fmt::Debug,
-    io::{Cursor, Write},
-};
+use bytes::Bytes;
+use error::{DecodeError, EncodeError};
+use reader::Reader;
+use std::{fmt::Debug, io::Write};
 
 ///
 /// Binary trait is implemented for all the data types, structs or enums that can be
 /// serialized or deserialized over the network stream.
 ///
-/// We do not need to return a `Result` whilst serializing because it's rare to crash on `panic!` while
-/// unwrapping.
+/// Serialization returns an `EncodeError` when the writer fails (a socket, a full fixed size slice, etc.)
+/// or when a value cannot be represented in its encoding, such as a length that does not fit its prefix.
 ///
-/// We can guarantee the serialization of data that we do on our end but we cannot guarantee the data
-/// coming from the other end is in the format we expect.
+/// We cannot guarantee the data coming from the other end is in the format we expect, so deserialization
+/// returns a `DecodeError` describing what went wrong and at which byte offset. The `Reader` enforces
+/// the `Limits` of the decode, so that lengths read off the wire cannot exhaust our memory, and it can be
+/// backed by a contiguous slice or by any `bytes::Buf`.
 ///
 pub trait Binary<'a>: Sized + Debug {
-    fn serialize(&self, buf: &mut impl Write);
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> std::io::Result<Self>;
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError>;
+
+    /// Returns the exact amount of bytes that `serialize` writes for this value. The default implementation
+    /// serializes the value into a writer that only counts the bytes, so types should override it whenever
+    /// the size can be computed directly.
+    fn encoded_len(&self) -> usize {
+        Counter::count(|buf| self.serialize(buf))
+    }
+
+    /// Serializes the value into a new buffer, allocating exactly `encoded_len` bytes once.
+    fn to_bytes(&self) -> Result<Bytes, EncodeError> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.serialize(&mut buf)?;
+
+        Ok(buf.into())
+    }
+}
+
+/// Writer that discards the data written to it and only counts the amount of bytes.
+pub struct Counter(usize);
+
+impl Counter {
+    /// Returns the amount of bytes that the function writes, used for the encoded length of values that
+    /// are serialized by functions, such as the fields with `#[binary(serialize_with = "...")]`.
+    pub fn count(write: impl FnOnce(&mut Counter) -> Result<(), EncodeError>) -> usize {
+        let mut counter = Counter(0);
+        let _ = write(&mut counter);
+
+        counter.0
+    }
+}
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
 ///
@@ -0,0 +1,1049 @@
+use crate::datatypes::{VarI32, VarI64, VarU32, F32, F64, I16, I32, I64, U16};
+use crate::error::{DecodeError, EncodeError, PathSegment};
+use crate::reader::Reader;
+use crate::Binary;
+use byteorder::{BE, LE};
+use std::borrow::Cow;
+use std::io::Write;
+use std::marker::PhantomData;
+
+pub const TAG_END: u8 = 0;
+pub const TAG_BYTE: u8 = 1;
+pub const TAG_SHORT: u8 = 2;
+pub const TAG_INT: u8 = 3;
+pub const TAG_LONG: u8 = 4;
+pub const TAG_FLOAT: u8 = 5;
+pub const TAG_DOUBLE: u8 = 6;
+pub const TAG_BYTE_ARRAY: u8 = 7;
+pub const TAG_STRING: u8 = 8;
+pub const TAG_LIST: u8 = 9;
+pub const TAG_COMPOUND: u8 = 10;
+pub const TAG_INT_ARRAY: u8 = 11;
+pub const TAG_LONG_ARRAY: u8 = 12;
+
+///
+/// NbtEncoding is implemented by the marker types for the flavours of NBT, which differ only in how
+/// numbers and lengths are encoded:
+///
+/// - `LittleEndian` is used by Bedrock for world files.
+/// - `NetworkLittleEndian` is used by Bedrock over the network. Ints, longs and lengths are varints.
+/// - `BigEndian` is used by Java edition.
+///
+/// The lengths of lists and arrays are encoded as ints, and the lengths of strings with their own encoding.
+///
+pub trait NbtEncoding {
+    fn write_i16(val: i16, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn read_i16<'a>(buf: &mut impl Reader<'a>) -> Result<i16, DecodeError>;
+
+    fn write_i32(val: i32, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn read_i32<'a>(buf: &mut impl Reader<'a>) -> Result<i32, DecodeError>;
+
+    fn write_i64(val: i64, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn read_i64<'a>(buf: &mut impl Reader<'a>) -> Result<i64, DecodeError>;
+
+    fn write_f32(val: f32, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn read_f32<'a>(buf: &mut impl Reader<'a>) -> Result<f32, DecodeError>;
+
+    fn write_f64(val: f64, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn read_f64<'a>(buf: &mut impl Reader<'a>) -> Result<f64, DecodeError>;
+
+    fn write_string_len(len: usize, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn read_string_len<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError>;
+}
+
+/// Little endian NBT, used by Bedrock for world files.
+pub struct LittleEndian;
+
+/// Little endian NBT with varint ints, longs and lengths, used by Bedrock over the network.
+pub struct NetworkLittleEndian;
+
+/// Big endian NBT, used by Java edition.
+pub struct BigEndian;
+
+///
+/// This macro implements `NbtEncoding` for the fixed width flavours in the provided byte order, with the
+/// length of strings encoded as an unsigned short.
+///
+macro_rules! fixed_encoding_impl {
+    ($name:ident, $order:ident) => {
+        impl NbtEncoding for $name {
+            fn write_i16(val: i16, buf: &mut impl Write) -> Result<(), EncodeError> {
+                I16::<$order>::new(val).serialize(buf)
+            }
+
+            fn read_i16<'a>(buf: &mut impl Reader<'a>) -> Result<i16, DecodeError> {
+                Ok(I16::<$order>::deserialize(buf)?.0)
+            }
+
+            fn write_i32(val: i32, buf: &mut impl Write) -> Result<(), EncodeError> {
+                I32::<$order>::new(val).serialize(buf)
+            }
+
+            fn read_i32<'a>(buf: &mut impl Reader<'a>) -> Result<i32, DecodeError> {
+                Ok(<I32<$order> as Binary>::deserialize(buf)?.0)
+            }
+
+            fn write_i64(val: i64, buf: &mut impl Write) -> Result<(), EncodeError> {
+                I64::<$order>::new(val).serialize(buf)
+            }
+
+            fn read_i64<'a>(buf: &mut impl Reader<'a>) -> Result<i64, DecodeError> {
+                Ok(I64::<$order>::deserialize(buf)?.0)
+            }
+
+            fn write_f32(val: f32, buf: &mut impl Write) -> Result<(), EncodeError> {
+                F32::<$order>::new(val).serialize(buf)
+            }
+
+            fn read_f32<'a>(buf: &mut impl Reader<'a>) -> Result<f32, DecodeError> {
+                Ok(F32::<$order>::deserialize(buf)?.0)
+            }
+
+            fn write_f64(val: f64, buf: &mut impl Write) -> Result<(), EncodeError> {
+                F64::<$order>::new(val).serialize(buf)
+            }
+
+            fn read_f64<'a>(buf: &mut impl Reader<'a>) -> Result<f64, DecodeError> {
+                Ok(F64::<$order>::deserialize(buf)?.0)
+            }
+
+            fn write_string_len(len: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+                <U16<$order> as crate::prefixed::Prefix>::encode(len, buf)
+            }
+
+            fn read_string_len<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+                Ok(<U16<$order> as Binary>::deserialize(buf)?.0 as usize)
+            }
+        }
+    };
+}
+
+fixed_encoding_impl!(LittleEndian, LE);
+fixed_encoding_impl!(BigEndian, BE);
+
+impl NbtEncoding for NetworkLittleEndian {
+    fn write_i16(val: i16, buf: &mut impl Write) -> Result<(), EncodeError> {
+        LittleEndian::write_i16(val, buf)
+    }
+
+    fn read_i16<'a>(buf: &mut impl Reader<'a>) -> Result<i16, DecodeError> {
+        LittleEndian::read_i16(buf)
+    }
+
+    fn write_i32(val: i32, buf: &mut impl Write) -> Result<(), EncodeError> {
+        VarI32::new(val).serialize(buf)
+    }
+
+    fn read_i32<'a>(buf: &mut impl Reader<'a>) -> Result<i32, DecodeError> {
+        Ok(<VarI32 as Binary>::deserialize(buf)?.0)
+    }
+
+    fn write_i64(val: i64, buf: &mut impl Write) -> Result<(), EncodeError> {
+        VarI64::new(val).serialize(buf)
+    }
+
+    fn read_i64<'a>(buf: &mut impl Reader<'a>) -> Result<i64, DecodeError> {
+        Ok(VarI64::deserialize(buf)?.0)
+    }
+
+    fn write_f32(val: f32, buf: &mut impl Write) -> Result<(), EncodeError> {
+        LittleEndian::write_f32(val, buf)
+    }
+
+    fn read_f32<'a>(buf: &mut impl Reader<'a>) -> Result<f32, DecodeError> {
+        LittleEndian::read_f32(buf)
+    }
+
+    fn write_f64(val: f64, buf: &mut impl Write) -> Result<(), EncodeError> {
+        LittleEndian::write_f64(val, buf)
+    }
+
+    fn read_f64<'a>(buf: &mut impl Reader<'a>) -> Result<f64, DecodeError> {
+        LittleEndian::read_f64(buf)
+    }
+
+    fn write_string_len(len: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        if len > i16::MAX as usize {
+            return Err(EncodeError::LengthOverflow {
+                len,
+                max: i16::MAX as usize,
+            });
+        }
+
+        VarU32::new(len as u32).serialize(buf)
+    }
+
+    fn read_string_len<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+        Ok(<VarU32 as Binary>::deserialize(buf)?.0 as usize)
+    }
+}
+
+/// Tag is a single NBT value. Strings and byte arrays borrow from the buffer when they are decoded from a
+/// contiguous slice, the same way as `Str` does, so `into_owned` has to be used to keep them around.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Cow<'a, [u8]>),
+    String(Cow<'a, str>),
+    /// A list of tags that all have the same type. Empty lists are written with the element type of
+    /// `TAG_END`.
+    List(Vec<Tag<'a>>),
+    Compound(Compound<'a>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// Compound is a collection of named tags. It keeps the tags in the order they were decoded or inserted
+/// in, so that decoding and encoding it again gives back the same bytes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Compound<'a>(Vec<(Cow<'a, str>, Tag<'a>)>);
+
+///
+/// Nbt is the root of an NBT tree: a tag, usually a compound, with a name that is usually empty. It
+/// implements `Binary` so that it can be used as a field of packets, with the type parameter `E`
+/// selecting the flavour of NBT.
+///
+/// Strings are encoded as plain UTF-8, as Bedrock does, not as the modified UTF-8 that Java uses.
+///
+pub struct Nbt<'a, E: NbtEncoding> {
+    pub name: Cow<'a, str>,
+    pub tag: Tag<'a>,
+    _marker: PhantomData<E>,
+}
+
+impl<'a> Tag<'a> {
+    /// Returns the type of the tag, one of the `TAG_*` constants.
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Byte(_) => TAG_BYTE,
+            Self::Short(_) => TAG_SHORT,
+            Self::Int(_) => TAG_INT,
+            Self::Long(_) => TAG_LONG,
+            Self::Float(_) => TAG_FLOAT,
+            Self::Double(_) => TAG_DOUBLE,
+            Self::ByteArray(_) => TAG_BYTE_ARRAY,
+            Self::String(_) => TAG_STRING,
+            Self::List(_) => TAG_LIST,
+            Self::Compound(_) => TAG_COMPOUND,
+            Self::IntArray(_) => TAG_INT_ARRAY,
+            Self::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Converts the tag into one that owns all of its strings and byte arrays.
+    pub fn into_owned(self) -> Tag<'static> {
+        match self {
+            Self::Byte(val) => Tag::Byte(val),
+            Self::Short(val) => Tag::Short(val),
+            Self::Int(val) => Tag::Int(val),
+            Self::Long(val) => Tag::Long(val),
+            Self::Float(val) => Tag::Float(val),
+            Self::Double(val) => Tag::Double(val),
+            Self::ByteArray(val) => Tag::ByteArray(Cow::Owned(val.into_owned())),
+            Self::String(val) => Tag::String(Cow::Owned(val.into_owned())),
+            Self::List(val) => Tag::List(val.into_iter().map(Tag::into_owned).collect()),
+            Self::Compound(val) => Tag::Compound(val.into_owned()),
+            Self::IntArray(val) => Tag::IntArray(val),
+            Self::LongArray(val) => Tag::LongArray(val),
+        }
+    }
+
+    pub fn as_byte(&self) -> Option<i8> {
+        match self {
+            Self::Byte(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_short(&self) -> Option<i16> {
+        match self {
+            Self::Short(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            Self::Int(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_long(&self) -> Option<i64> {
+        match self {
+            Self::Long(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Self::Float(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self {
+            Self::Double(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag<'a>]> {
+        match self {
+            Self::List(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound<'a>> {
+        match self {
+            Self::Compound(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    fn write<E: NbtEncoding>(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        match self {
+            Self::Byte(val) => buf.write_all(&[*val as u8])?,
+            Self::Short(val) => E::write_i16(*val, buf)?,
+            Self::Int(val) => E::write_i32(*val, buf)?,
+            Self::Long(val) => E::write_i64(*val, buf)?,
+            Self::Float(val) => E::write_f32(*val, buf)?,
+            Self::Double(val) => E::write_f64(*val, buf)?,
+            Self::ByteArray(val) => {
+                write_len::<E>(val.len(), buf)?;
+                buf.write_all(val)?;
+            }
+            Self::String(val) => write_string::<E>(val, buf)?,
+            Self::List(val) => {
+                let id = val.first().map_or(TAG_END, Tag::id);
+
+                if val.iter().any(|tag| tag.id() != id) {
+                    return Err(EncodeError::Custom(String::from(
+                        "NBT list contains tags of different types",
+                    )));
+                }
+
+                buf.write_all(&[id])?;
+                write_len::<E>(val.len(), buf)?;
+
+                for tag in val {
+                    tag.write::<E>(buf)?;
+                }
+            }
+            Self::Compound(val) => val.write::<E>(buf)?,
+            Self::IntArray(val) => {
+                write_len::<E>(val.len(), buf)?;
+
+                for int in val {
+                    E::write_i32(*int, buf)?;
+                }
+            }
+            Self::LongArray(val) => {
+                write_len::<E>(val.len(), buf)?;
+
+                for long in val {
+                    E::write_i64(*long, buf)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the value of a tag whose id has been checked by `read_id`.
+    fn read<E: NbtEncoding>(id: u8, buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let tag = match id {
+            TAG_BYTE => Self::Byte(buf.read_u8()? as i8),
+            TAG_SHORT => Self::Short(E::read_i16(buf)?),
+            TAG_INT => Self::Int(E::read_i32(buf)?),
+            TAG_LONG => Self::Long(E::read_i64(buf)?),
+            TAG_FLOAT => Self::Float(E::read_f32(buf)?),
+            TAG_DOUBLE => Self::Double(E::read_f64(buf)?),
+            TAG_BYTE_ARRAY => {
+                let offset = buf.position();
+                let len = read_len::<E>(buf)?;
+                buf.check_elements(offset, len)?;
+
+                Self::ByteArray(buf.read_bytes(len)?)
+            }
+            TAG_STRING => Self::String(read_string::<E>(buf)?),
+            TAG_LIST => buf.nested(|buf| {
+                let offset = buf.position();
+                let id = read_id(buf)?;
+                let len = read_len::<E>(buf)?;
+                buf.check_elements(offset, len)?;
+
+                if id == TAG_END && len > 0 {
+                    return Err(DecodeError::InvalidDiscriminant {
+                        offset,
+                        value: id as i128,
+                    });
+                }
+
+                let mut list = Vec::with_capacity(len.min(buf.remaining()));
+
+                for _ in 0..len {
+                    list.push(Self::read::<E>(id, buf)?);
+                }
+
+                Ok(Self::List(list))
+            })?,
+            TAG_COMPOUND => Self::Compound(buf.nested(Compound::read::<E>)?),
+            TAG_INT_ARRAY => {
+                let offset = buf.position();
+                let len = read_len::<E>(buf)?;
+                buf.check_elements(offset, len)?;
+
+                let mut array = Vec::with_capacity(len.min(buf.remaining()));
+                for _ in 0..len {
+                    array.push(E::read_i32(buf)?);
+                }
+
+                Self::IntArray(array)
+            }
+            TAG_LONG_ARRAY => {
+                let offset = buf.position();
+                let len = read_len::<E>(buf)?;
+                buf.check_elements(offset, len)?;
+
+                let mut array = Vec::with_capacity(len.min(buf.remaining()));
+                for _ in 0..len {
+                    array.push(E::read_i64(buf)?);
+                }
+
+                Self::LongArray(array)
+            }
+            // `TAG_END` has no value, so it is handled by the callers.
+            _ => unreachable!("tag id {} has no value", id),
+        };
+
+        Ok(tag)
+    }
+}
+
+impl<'a> Compound<'a> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Returns the tag with the provided name.
+    pub fn get(&self, name: &str) -> Option<&Tag<'a>> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, tag)| tag)
+    }
+
+    /// Returns a mutable reference to the tag with the provided name.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Tag<'a>> {
+        self.0
+            .iter_mut()
+            .find(|(key, _)| key == name)
+            .map(|(_, tag)| tag)
+    }
+
+    /// Inserts a tag, replacing the tag with the same name and returning it if there was one.
+    pub fn insert(
+        &mut self,
+        name: impl Into<Cow<'a, str>>,
+        tag: impl Into<Tag<'a>>,
+    ) -> Option<Tag<'a>> {
+        let name = name.into();
+        let tag = tag.into();
+
+        match self.get_mut(&name) {
+            Some(old) => Some(std::mem::replace(old, tag)),
+            None => {
+                self.0.push((name, tag));
+                None
+            }
+        }
+    }
+
+    /// Removes the tag with the provided name and returns it.
+    pub fn remove(&mut self, name: &str) -> Option<Tag<'a>> {
+        let index = self.0.iter().position(|(key, _)| key == name)?;
+        Some(self.0.remove(index).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the names and tags in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Tag<'a>)> {
+        self.0.iter().map(|(key, tag)| (key.as_ref(), tag))
+    }
+
+    /// Converts the compound into one that owns all of its strings and byte arrays.
+    pub fn into_owned(self) -> Compound<'static> {
+        Compound(
+            self.0
+                .into_iter()
+                .map(|(key, tag)| (Cow::Owned(key.into_owned()), tag.into_owned()))
+                .collect(),
+        )
+    }
+
+    fn write<E: NbtEncoding>(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        for (name, tag) in &self.0 {
+            buf.write_all(&[tag.id()])?;
+            write_string::<E>(name, buf)?;
+            tag.write::<E>(buf)?;
+        }
+
+        buf.write_all(&[TAG_END])?;
+        Ok(())
+    }
+
+    fn read<E: NbtEncoding>(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let mut compound = Vec::new();
+
+        loop {
+            let offset = buf.position();
+            let id = read_id(buf)?;
+
+            if id == TAG_END {
+                return Ok(Self(compound));
+            }
+
+            buf.check_elements(offset, compound.len() + 1)?;
+
+            let name = read_string::<E>(buf)?;
+            let tag = Tag::read::<E>(id, buf)?;
+            compound.push((name, tag));
+        }
+    }
+}
+
+impl<'a, E: NbtEncoding> Nbt<'a, E> {
+    /// Creates a root tag with the provided name.
+    pub fn new(name: impl Into<Cow<'a, str>>, tag: impl Into<Tag<'a>>) -> Self {
+        Self {
+            name: name.into(),
+            tag: tag.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a root compound with an empty name, which is what Bedrock sends in almost all packets.
+    pub fn compound(compound: Compound<'a>) -> Self {
+        Self::new("", compound)
+    }
+
+    /// Converts the tree into one that owns all of its strings and byte arrays.
+    pub fn into_owned(self) -> Nbt<'static, E> {
+        Nbt {
+            name: Cow::Owned(self.name.into_owned()),
+            tag: self.tag.into_owned(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E: NbtEncoding> std::fmt::Debug for Nbt<'a, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Nbt")
+            .field("name", &self.name)
+            .field("tag", &self.tag)
+            .finish()
+    }
+}
+
+impl<'a, E: NbtEncoding> Clone for Nbt<'a, E> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone(), self.tag.clone())
+    }
+}
+
+impl<'a, E: NbtEncoding> PartialEq for Nbt<'a, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.tag == other.tag
+    }
+}
+
+impl<'a, E: NbtEncoding> Binary<'a> for Nbt<'a, E> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_all(&[self.tag.id()])?;
+        write_string::<E>(&self.name, buf)?;
+        self.tag.write::<E>(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
+        let id = read_id(buf)?;
+
+        if id == TAG_END {
+            return Err(DecodeError::InvalidDiscriminant {
+                offset,
+                value: id as i128,
+            });
+        }
+
+        let name = read_string::<E>(buf)?;
+        let tag = Tag::read::<E>(id, buf)?;
+
+        Ok(Self::new(name, tag))
+    }
+}
+
+impl<'a> FromIterator<(Cow<'a, str>, Tag<'a>)> for Compound<'a> {
+    fn from_iter<T: IntoIterator<Item = (Cow<'a, str>, Tag<'a>)>>(iter: T) -> Self {
+        let mut compound = Self::new();
+
+        for (name, tag) in iter {
+            compound.insert(name, tag);
+        }
+
+        compound
+    }
+}
+
+impl<'a> IntoIterator for Compound<'a> {
+    type Item = (Cow<'a, str>, Tag<'a>);
+    type IntoIter = std::vec::IntoIter<(Cow<'a, str>, Tag<'a>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+///
+/// This macro implements the conversions of values into the tags that hold them.
+///
+macro_rules! tag_from_impl {
+    ($($ty:ty => $variant:ident),+ $(,)?) => {
+        $(
+            impl<'a> From<$ty> for Tag<'a> {
+                fn from(val: $ty) -> Self {
+                    Self::$variant(val.into())
+                }
+            }
+        )+
+    };
+}
+
+tag_from_impl!(
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    &'a str => String,
+    String => String,
+    Cow<'a, str> => String,
+    Vec<Tag<'a>> => List,
+    Compound<'a> => Compound,
+    Vec<i32> => IntArray,
+    Vec<i64> => LongArray,
+);
+
+impl<'a> From<bool> for Tag<'a> {
+    /// Booleans are stored as bytes.
+    fn from(val: bool) -> Self {
+        Self::Byte(val as i8)
+    }
+}
+
+///
+/// ToTag is implemented by values that can be written as a tag, including the structs that derive `Nbt`,
+/// which are written as compounds. Strings are borrowed from the value instead of being copied.
+///
+pub trait ToTag {
+    fn to_tag(&self) -> Tag<'_>;
+}
+
+///
+/// FromTag is implemented by values that can be read from a tag, including the structs that derive `Nbt`,
+/// which are read from compounds. The tag is taken by value so that borrowed strings can be moved into
+/// the value.
+///
+pub trait FromTag<'a>: Sized {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError>;
+}
+
+/// TagError is returned by `FromTag::from_tag` when a tag does not have the shape of the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagError {
+    /// A field that is not optional and has no default was missing from the compound.
+    MissingField { field: &'static str },
+    /// The type of a tag was different from the one of the value.
+    UnexpectedType { expected: u8, found: u8 },
+    /// The error occurred while reading the field of a compound.
+    InField {
+        field: &'static str,
+        error: Box<TagError>,
+    },
+}
+
+impl TagError {
+    /// Wraps the error into the field it occurred in.
+    pub fn in_field(self, field: &'static str) -> Self {
+        Self::InField {
+            field,
+            error: Box::new(self),
+        }
+    }
+
+    /// Converts the error of reading the type `ty` from the tag decoded at the offset into a
+    /// `DecodeError`, with the fields that it occurred in as the path of the error.
+    pub fn into_decode_error(self, ty: &'static str, offset: usize) -> DecodeError {
+        let mut path = Vec::new();
+        let mut error = self;
+
+        while let Self::InField {
+            field,
+            error: inner,
+        } = error
+        {
+            path.push(PathSegment::Field(field));
+            error = *inner;
+        }
+
+        let error = DecodeError::Nbt { offset, error };
+
+        match path.is_empty() {
+            true => error,
+            false => DecodeError::InField {
+                ty: Some(ty),
+                path,
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingField { field } => write!(f, "missing field `{}`", field),
+            Self::UnexpectedType { expected, found } => {
+                write!(f, "expected tag of type {}, found {}", expected, found)
+            }
+            Self::InField { field, error } => write!(f, "field `{}`: {}", field, error),
+        }
+    }
+}
+
+impl std::error::Error for TagError {}
+
+///
+/// This macro implements `ToTag` and `FromTag` for the values that are stored in a single variant of
+/// `Tag`.
+///
+macro_rules! tag_value_impl {
+    ($($ty:ty => $variant:ident),+ $(,)?) => {
+        $(
+            impl ToTag for $ty {
+                fn to_tag(&self) -> Tag<'_> {
+                    Tag::$variant(*self)
+                }
+            }
+
+            impl<'a> FromTag<'a> for $ty {
+                fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+                    match tag {
+                        Tag::$variant(val) => Ok(val),
+                        tag => Err(TagError::UnexpectedType {
+                            expected: Tag::$variant(Default::default()).id(),
+                            found: tag.id(),
+                        }),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+tag_value_impl!(
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+);
+
+impl ToTag for bool {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::Byte(*self as i8)
+    }
+}
+
+impl<'a> FromTag<'a> for bool {
+    /// Any byte other than zero is true.
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        Ok(i8::from_tag(tag)? != 0)
+    }
+}
+
+impl ToTag for str {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::String(Cow::Borrowed(self))
+    }
+}
+
+impl ToTag for String {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::String(Cow::Borrowed(self))
+    }
+}
+
+impl<'a> FromTag<'a> for String {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        Ok(Cow::<str>::from_tag(tag)?.into_owned())
+    }
+}
+
+impl<'b> ToTag for Cow<'b, str> {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::String(Cow::Borrowed(self))
+    }
+}
+
+impl<'a> FromTag<'a> for Cow<'a, str> {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        match tag {
+            Tag::String(val) => Ok(val),
+            tag => Err(TagError::UnexpectedType {
+                expected: TAG_STRING,
+                found: tag.id(),
+            }),
+        }
+    }
+}
+
+impl<T: ToTag> ToTag for Vec<T> {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::List(self.iter().map(T::to_tag).collect())
+    }
+}
+
+impl<'a, T: FromTag<'a>> FromTag<'a> for Vec<T> {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        match tag {
+            Tag::List(val) => val.into_iter().map(T::from_tag).collect(),
+            // The elements of arrays are stored without their tags, so they are wrapped into one to be
+            // read the same way as the elements of a list.
+            Tag::ByteArray(val) => val
+                .iter()
+                .map(|&val| T::from_tag(Tag::Byte(val as i8)))
+                .collect(),
+            Tag::IntArray(val) => val
+                .into_iter()
+                .map(|val| T::from_tag(Tag::Int(val)))
+                .collect(),
+            Tag::LongArray(val) => val
+                .into_iter()
+                .map(|val| T::from_tag(Tag::Long(val)))
+                .collect(),
+            tag => Err(TagError::UnexpectedType {
+                expected: TAG_LIST,
+                found: tag.id(),
+            }),
+        }
+    }
+}
+
+///
+/// ByteArray is written as a `TAG_BYTE_ARRAY` instead of the list of bytes that `Vec<i8>` is written as.
+/// The bytes are borrowed from the buffer when they are decoded from a contiguous slice.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ByteArray<'a>(pub Cow<'a, [u8]>);
+
+impl<'b> ToTag for ByteArray<'b> {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::ByteArray(Cow::Borrowed(&self.0))
+    }
+}
+
+impl<'a> FromTag<'a> for ByteArray<'a> {
+    /// Lists of bytes are read as well.
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        match tag {
+            Tag::ByteArray(val) => Ok(Self(val)),
+            Tag::List(val) => val
+                .into_iter()
+                .map(|tag| i8::from_tag(tag).map(|val| val as u8))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|val| Self(Cow::Owned(val))),
+            tag => Err(TagError::UnexpectedType {
+                expected: TAG_BYTE_ARRAY,
+                found: tag.id(),
+            }),
+        }
+    }
+}
+
+///
+/// This macro implements `ToTag` and `FromTag` for the newtypes that are written as an array tag instead
+/// of a list.
+///
+macro_rules! array_impl {
+    ($($(#[$attr:meta])* $name:ident($ty:ty) => $variant:ident, $id:expr;)+) => {
+        $(
+            $(#[$attr])*
+            #[derive(Debug, Clone, Default, PartialEq, Eq)]
+            pub struct $name(pub Vec<$ty>);
+
+            impl ToTag for $name {
+                fn to_tag(&self) -> Tag<'_> {
+                    Tag::$variant(self.0.clone())
+                }
+            }
+
+            impl<'a> FromTag<'a> for $name {
+                /// Lists of the elements are read as well.
+                fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+                    match tag {
+                        Tag::$variant(val) => Ok(Self(val)),
+                        Tag::List(val) => val.into_iter().map(<$ty>::from_tag).collect::<Result<_, _>>().map(Self),
+                        tag => Err(TagError::UnexpectedType {
+                            expected: $id,
+                            found: tag.id(),
+                        }),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+array_impl! {
+    /// IntArray is written as a `TAG_INT_ARRAY` instead of the list of ints that `Vec<i32>` is written as.
+    IntArray(i32) => IntArray, TAG_INT_ARRAY;
+    /// LongArray is written as a `TAG_LONG_ARRAY` instead of the list of longs that `Vec<i64>` is written
+    /// as.
+    LongArray(i64) => LongArray, TAG_LONG_ARRAY;
+}
+
+impl<T: ToTag> ToTag for Box<T> {
+    fn to_tag(&self) -> Tag<'_> {
+        T::to_tag(self)
+    }
+}
+
+impl<'a, T: FromTag<'a>> FromTag<'a> for Box<T> {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        Ok(Box::new(T::from_tag(tag)?))
+    }
+}
+
+impl<'b> ToTag for Compound<'b> {
+    fn to_tag(&self) -> Tag<'_> {
+        Tag::Compound(self.clone())
+    }
+}
+
+impl<'a> FromTag<'a> for Compound<'a> {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        match tag {
+            Tag::Compound(val) => Ok(val),
+            tag => Err(TagError::UnexpectedType {
+                expected: TAG_COMPOUND,
+                found: tag.id(),
+            }),
+        }
+    }
+}
+
+impl<'b> ToTag for Tag<'b> {
+    fn to_tag(&self) -> Tag<'_> {
+        self.clone()
+    }
+}
+
+impl<'a> FromTag<'a> for Tag<'a> {
+    fn from_tag(tag: Tag<'a>) -> Result<Self, TagError> {
+        Ok(tag)
+    }
+}
+
+/// Writes the length of a list or an array, which is encoded as an int.
+fn write_len<E: NbtEncoding>(len: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+    let len = i32::try_from(len).map_err(|_| EncodeError::LengthOverflow {
+        len,
+        max: i32::MAX as usize,
+    })?;
+
+    E::write_i32(len, buf)
+}
+
+/// Reads the id of a tag, rejecting the ids that are not known, so that the error is reported at the id
+/// instead of at the name or the length that follows it.
+fn read_id<'a>(buf: &mut impl Reader<'a>) -> Result<u8, DecodeError> {
+    let offset = buf.position();
+    let id = buf.read_u8()?;
+
+    if id > TAG_LONG_ARRAY {
+        return Err(DecodeError::InvalidDiscriminant {
+            offset,
+            value: id as i128,
+        });
+    }
+
+    Ok(id)
+}
+
+/// Reads the length of a list or an array, rejecting negative lengths.
+fn read_len<'a, E: NbtEncoding>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+    let offset = buf.position();
+    let len = E::read_i32(buf)?;
+
+    usize::try_from(len).map_err(|_| DecodeError::NegativeLength {
+        offset,
+        len: len as i64,
+    })
+}
+
+fn write_string<E: NbtEncoding>(val: &str, buf: &mut impl Write) -> Result<(), EncodeError> {
+    E::write_string_len(val.len(), buf)?;
+    buf.write_all(val.as_bytes())?;
+    Ok(())
+}
+
+/// Reads a string, borrowing it from the buffer when possible the same way as `Str` does.
+fn read_string<'a, E: NbtEncoding>(buf: &mut impl Reader<'a>) -> Result<Cow<'a, str>, DecodeError> {
+    let offset = buf.position();
+    let len = E::read_string_len(buf)?;
+    buf.check_string_len(offset, len)?;
+
+    let offset = buf.position();
+    let val = match buf.read_bytes(len)? {
+        Cow::Borrowed(bytes) => Cow::Borrowed(
+            std::str::from_utf8(bytes)
+                .map_err(|error| DecodeError::InvalidUtf8 { offset, error })?,
+        ),
+        Cow::Owned(bytes) => {
+            Cow::Owned(
+                String::from_utf8(bytes).map_err(|err| DecodeError::InvalidUtf8 {
+                    offset,
+                    error: err.utf8_error(),
+                })?,
+            )
+        }
+    };
+
+    Ok(val)
+}
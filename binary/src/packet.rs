@@ -0,0 +1,169 @@
+use crate::datatypes::VarU32;
+use crate::error::{DecodeError, EncodeError};
+use crate::prefixed::UnsizedBytes;
+use crate::reader::Reader;
+use crate::Binary;
+use std::io::Write;
+
+/// The largest id that fits into the bits of the header reserved for it.
+pub const MAX_ID: u32 = 0x3FF;
+/// The largest sub-client id that fits into the bits of the header reserved for it.
+pub const MAX_SUB_CLIENT: u8 = 0x03;
+
+const SENDER_SHIFT: u32 = 10;
+const TARGET_SHIFT: u32 = 12;
+/// The amount of bits used by the header, above which no bit can be set.
+const HEADER_BITS: u32 = 14;
+
+///
+/// Packet is implemented by the game packets, which are encoded after a `Header` holding their id. It is
+/// implemented with `#[derive(Packet)]` and `#[packet(id = ...)]`.
+///
+pub trait Packet {
+    const ID: u32;
+}
+
+///
+/// Header precedes every game packet. It is a `VarU32` holding the id of the packet in the lowest 10 bits,
+/// followed by 2 bits for the sub-client that sent the packet and 2 bits for the one it is sent to, which
+/// are used for split screen.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Header {
+    pub id: u32,
+    pub sender: u8,
+    pub target: u8,
+}
+
+impl Header {
+    /// Creates the header of a packet sent between the main clients.
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            sender: 0,
+            target: 0,
+        }
+    }
+
+    fn value(&self) -> VarU32 {
+        VarU32::new(
+            self.id | (self.sender as u32) << SENDER_SHIFT | (self.target as u32) << TARGET_SHIFT,
+        )
+    }
+}
+
+impl<'a> Binary<'a> for Header {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        if self.id > MAX_ID || self.sender > MAX_SUB_CLIENT || self.target > MAX_SUB_CLIENT {
+            return Err(EncodeError::Custom(format!(
+                "header {:?} does not fit into the bits reserved for it",
+                self
+            )));
+        }
+
+        self.value().serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
+        let val = VarU32::deserialize(buf)?.0;
+
+        if val >> HEADER_BITS != 0 {
+            return Err(DecodeError::Custom {
+                offset,
+                message: format!("header {:#x} has bits set above the sub-client ids", val),
+            });
+        }
+
+        Ok(Self {
+            id: val & MAX_ID,
+            sender: (val >> SENDER_SHIFT) as u8 & MAX_SUB_CLIENT,
+            target: (val >> TARGET_SHIFT) as u8 & MAX_SUB_CLIENT,
+        })
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.value().encoded_len()
+    }
+}
+
+///
+/// Registry is implemented by the enums of packets that the `registry!` macro generates, which dispatch
+/// the data of a packet to the type registered for its id.
+///
+pub trait Registry<'a>: Sized {
+    /// Returns the id of the packet held.
+    fn id(&self) -> u32;
+    /// Decodes the packet registered for the id from the data that follows the header. Packets with ids
+    /// that are not registered are decoded as an `UnknownPacket`, which holds all of the data. The data
+    /// left after a registered packet is not read, unless the registry is declared with
+    /// `#[registry(deny_trailing_bytes)]`, which makes it a `DecodeError::TrailingBytes`.
+    fn decode(id: u32, buf: &mut impl Reader<'a>) -> Result<Self, DecodeError>;
+    /// Serializes the packet held, without the header.
+    fn encode(&self, buf: &mut impl Write) -> Result<(), EncodeError>;
+    /// Returns the amount of bytes that `encode` writes.
+    fn encoded_len(&self) -> usize;
+}
+
+///
+/// UnknownPacket holds a packet whose id is not registered with the data that follows its header, so
+/// that it can be reported or forwarded as it is.
+///
+#[derive(Debug, Clone)]
+pub struct UnknownPacket<'a> {
+    pub id: u32,
+    pub data: UnsizedBytes<'a>,
+}
+
+///
+/// Framed is a packet together with its header, as game packets are written in a batch. The id of the
+/// header is the one of the packet held.
+///
+#[derive(Debug, Clone)]
+pub struct Framed<R> {
+    pub sender: u8,
+    pub target: u8,
+    pub packet: R,
+}
+
+impl<R> Framed<R> {
+    /// Creates the frame of a packet sent between the main clients.
+    pub fn new(packet: R) -> Self {
+        Self {
+            sender: 0,
+            target: 0,
+            packet,
+        }
+    }
+}
+
+impl<'a, R: Registry<'a>> Framed<R> {
+    fn header(&self) -> Header {
+        Header {
+            id: self.packet.id(),
+            sender: self.sender,
+            target: self.target,
+        }
+    }
+}
+
+impl<'a, R: Registry<'a> + std::fmt::Debug> Binary<'a> for Framed<R> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        self.header().serialize(buf)?;
+        self.packet.encode(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let header = Header::deserialize(buf)?;
+
+        Ok(Self {
+            sender: header.sender,
+            target: header.target,
+            packet: R::decode(header.id, buf)?,
+        })
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.header().encoded_len() + self.packet.encoded_len()
+    }
+}
@@ -1,122 +1,219 @@
-use crate::datatypes::{VarI32, VarU32, I16, I32, U16, U32};
-use crate::{debug_impl_tt, Binary};
+use crate::datatypes::{varint_len, zigzag32, Strict, VarI32, VarU32, I16, I32, U16, U32};
+use crate::error::{DecodeError, EncodeError};
+use crate::reader::{Reader, VarIntMode};
+use crate::{debug_impl, debug_impl_tt, Binary};
 use byteorder::ByteOrder;
-use bytes::Buf;
-use std::io::{Cursor, Result, Write};
+use std::borrow::Cow;
+use std::io::Write;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
 /// Prefix trait is implemented for the numeric data types that can be used
 /// to encode the length of prefixed objects like Arrays, Vectors, Strings, etc.
 pub trait Prefix {
-    fn encode(size: usize, buf: &mut impl Write);
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize>;
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError>;
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError>;
+
+    /// Returns the amount of bytes that the prefix takes when encoding the provided size.
+    fn encoded_len(size: usize) -> usize;
 }
 
 impl<E: ByteOrder> Prefix for U16<E> {
-    fn encode(size: usize, buf: &mut impl Write) {
-        let val = size as u16;
-        U16::<E>::new(val).serialize(buf);
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let val = fit::<u16>(size, u16::MAX as usize)?;
+        U16::<E>::new(val).serialize(buf)
     }
 
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize> {
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
         let val = U16::<E>::deserialize(buf)?.0;
         Ok(val as usize)
     }
+
+    fn encoded_len(_size: usize) -> usize {
+        2
+    }
 }
 
 impl<E: ByteOrder> Prefix for I16<E> {
-    fn encode(size: usize, buf: &mut impl Write) {
-        let val = size as i16;
-        I16::<E>::new(val).serialize(buf);
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let val = fit::<i16>(size, i16::MAX as usize)?;
+        I16::<E>::new(val).serialize(buf)
     }
 
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize> {
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+        let offset = buf.position();
         let val = I16::<E>::deserialize(buf)?.0;
-        Ok(val as usize)
+        non_negative(offset, val as i64)
+    }
+
+    fn encoded_len(_size: usize) -> usize {
+        2
     }
 }
 
 impl<E: ByteOrder> Prefix for I32<E> {
-    fn encode(size: usize, buf: &mut impl Write) {
-        let val = size as i32;
-        I32::<E>::new(val).serialize(buf);
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let val = fit::<i32>(size, i32::MAX as usize)?;
+        I32::<E>::new(val).serialize(buf)
     }
 
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize> {
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+        let offset = buf.position();
         let val = I32::<E>::deserialize(buf)?.0;
-        Ok(val as usize)
+        non_negative(offset, val as i64)
+    }
+
+    fn encoded_len(_size: usize) -> usize {
+        4
     }
 }
 
 impl<E: ByteOrder> Prefix for U32<E> {
-    fn encode(size: usize, buf: &mut impl Write) {
-        let val = size as u32;
-        U32::<E>::new(val).serialize(buf);
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let val = fit::<u32>(size, u32::MAX as usize)?;
+        U32::<E>::new(val).serialize(buf)
     }
 
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize> {
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
         let val = U32::<E>::deserialize(buf)?.0;
         Ok(val as usize)
     }
+
+    fn encoded_len(_size: usize) -> usize {
+        4
+    }
 }
 
 impl Prefix for VarI32 {
-    fn encode(size: usize, buf: &mut impl Write) {
-        let val = size as i32;
-        VarI32::new(val).serialize(buf);
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let val = fit::<i32>(size, i32::MAX as usize)?;
+        VarI32::new(val).serialize(buf)
     }
 
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize> {
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+        let offset = buf.position();
         let val = VarI32::deserialize(buf)?.0;
-        Ok(val as usize)
+        non_negative(offset, val as i64)
+    }
+
+    fn encoded_len(size: usize) -> usize {
+        varint_len(zigzag32(size as i32) as u64)
     }
 }
 
 impl Prefix for VarU32 {
-    fn encode(size: usize, buf: &mut impl Write) {
-        let val = size as u32;
-        VarU32::new(val).serialize(buf);
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let val = fit::<u32>(size, u32::MAX as usize)?;
+        VarU32::new(val).serialize(buf)
     }
 
-    fn decode<'a>(buf: &mut Cursor<&'a [u8]>) -> Result<usize> {
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
         let val = VarU32::deserialize(buf)?.0;
         Ok(val as usize)
     }
+
+    fn encoded_len(size: usize) -> usize {
+        varint_len(size as u64)
+    }
+}
+
+impl Prefix for Strict<VarI32> {
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        VarI32::encode(size, buf)
+    }
+
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+        let offset = buf.position();
+        let val = VarI32::deserialize_in(buf, VarIntMode::Strict)?.0;
+        non_negative(offset, val as i64)
+    }
+
+    fn encoded_len(size: usize) -> usize {
+        <VarI32 as Prefix>::encoded_len(size)
+    }
+}
+
+impl Prefix for Strict<VarU32> {
+    fn encode(size: usize, buf: &mut impl Write) -> Result<(), EncodeError> {
+        VarU32::encode(size, buf)
+    }
+
+    fn decode<'a>(buf: &mut impl Reader<'a>) -> Result<usize, DecodeError> {
+        let val = VarU32::deserialize_in(buf, VarIntMode::Strict)?.0;
+        Ok(val as usize)
+    }
+
+    fn encoded_len(size: usize) -> usize {
+        <VarU32 as Prefix>::encoded_len(size)
+    }
 }
 
-/// Custom String Type with a generic for the Prefix type.
+/// Converts the size into the integer type used by a prefix, returning an error when it does not fit
+/// instead of silently truncating it.
+fn fit<T: TryFrom<usize>>(size: usize, max: usize) -> Result<T, EncodeError> {
+    T::try_from(size).map_err(|_| EncodeError::LengthOverflow { len: size, max })
+}
+
+/// Converts a length read from a signed prefix, rejecting negative values that would otherwise wrap
+/// around into enormous lengths.
+fn non_negative(offset: usize, len: i64) -> Result<usize, DecodeError> {
+    usize::try_from(len).map_err(|_| DecodeError::NegativeLength { offset, len })
+}
+
+/// Custom String Type with a generic for the Prefix type. It borrows the string from the buffer when
+/// decoding from a contiguous slice and owns a copy of it otherwise.
 #[derive(Clone, Default, PartialEq, Eq)]
-pub struct Str<'a, P: Prefix>(&'a str, PhantomData<P>);
+pub struct Str<'a, P: Prefix>(Cow<'a, str>, PhantomData<P>);
 
 impl<'a, P: Prefix> Str<'a, P> {
     pub fn new(val: &'a str) -> Self {
-        Self(val, PhantomData)
+        Self(Cow::Borrowed(val), PhantomData)
+    }
+
+    pub fn into_inner(self) -> Cow<'a, str> {
+        self.0
     }
 }
 
 impl<'a, P: Prefix> Binary<'a> for Str<'a, P> {
-    fn serialize(&self, buf: &mut impl Write) {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
         let len = self.0.len();
-        P::encode(len, buf);
+        P::encode(len, buf)?;
 
-        buf.write_all(self.0.as_bytes()).unwrap();
+        buf.write_all(self.0.as_bytes())?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
         let len = P::decode(buf)?;
-        let start = buf.position() as usize;
-        let end = start + len;
-
-        buf.advance(len);
+        buf.check_string_len(offset, len)?;
+
+        let offset = buf.position();
+        let val =
+            match buf.read_bytes(len)? {
+                Cow::Borrowed(bytes) => Cow::Borrowed(
+                    std::str::from_utf8(bytes)
+                        .map_err(|error| DecodeError::InvalidUtf8 { offset, error })?,
+                ),
+                Cow::Owned(bytes) => Cow::Owned(String::from_utf8(bytes).map_err(|err| {
+                    DecodeError::InvalidUtf8 {
+                        offset,
+                        error: err.utf8_error(),
+                    }
+                })?),
+            };
+
+        Ok(Self(val, PhantomData))
+    }
 
-        let val = std::str::from_utf8(&buf.get_ref()[start..end]).unwrap();
-        Ok(Self::new(val))
+    fn encoded_len(&self) -> usize {
+        P::encoded_len(self.0.len()) + self.0.len()
     }
 }
 
 impl<'a, P: Prefix> Deref for Str<'a, P> {
-    type Target = &'a str;
+    type Target = Cow<'a, str>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -135,24 +232,37 @@ impl<'a, B: Binary<'a>, P: Prefix> Array<'a, B, P> {
 }
 
 impl<'a, B: Binary<'a>, P: Prefix> Binary<'a> for Array<'a, B, P> {
-    fn serialize(&self, buf: &mut impl Write) {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
         let len = self.0.len();
-        P::encode(len, buf);
+        P::encode(len, buf)?;
 
         for element in &self.0 {
-            element.serialize(buf);
+            element.serialize(buf)?;
         }
+
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
         let len = P::decode(buf)?;
-        let mut array = Vec::with_capacity(len);
+        buf.check_elements(offset, len)?;
 
-        for i in 0..len {
-            array.insert(i, B::deserialize(buf)?);
-        }
+        buf.nested(|buf| {
+            // Every element takes at least a byte on the wire in practice, so the amount of bytes
+            // remaining bounds how much we preallocate for a length that has not been read yet.
+            let mut array = Vec::with_capacity(len.min(buf.remaining()));
+
+            for i in 0..len {
+                array.push(B::deserialize(buf).map_err(|err| err.in_element(i))?);
+            }
+
+            Ok(Self::new(array))
+        })
+    }
 
-        Ok(Self::new(array))
+    fn encoded_len(&self) -> usize {
+        P::encoded_len(self.0.len()) + self.0.iter().map(B::encoded_len).sum::<usize>()
     }
 }
 
@@ -174,29 +284,36 @@ impl<'a, B: Binary<'a>, P: Prefix> DerefMut for Array<'a, B, P> {
 /// It reads the complete remaining portion of the buffer as a slice, so use this only when you want
 /// to read a slice from the end of the buffer that does not contain anything else after the slice.
 #[derive(Clone, Default, PartialEq, Eq)]
-pub struct UnsizedBytes<'a>(&'a [u8]);
+pub struct UnsizedBytes<'a>(Cow<'a, [u8]>);
 
 impl<'a> UnsizedBytes<'a> {
     pub fn new(data: &'a [u8]) -> Self {
-        Self(data)
+        Self(Cow::Borrowed(data))
+    }
+
+    pub fn into_inner(self) -> Cow<'a, [u8]> {
+        self.0
     }
 }
 
 impl<'a> Binary<'a> for UnsizedBytes<'a> {
-    fn serialize(&self, buf: &mut impl Write) {
-        buf.write_all(&self.0).unwrap();
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_all(&self.0)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Cursor<&'a [u8]>) -> Result<Self> {
-        let start = buf.position() as usize;
-        let end = buf.remaining() + start;
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let len = buf.remaining();
+        Ok(Self(buf.read_bytes(len)?))
+    }
 
-        Ok(Self::new(&buf.get_ref()[start..end]))
+    fn encoded_len(&self) -> usize {
+        self.0.len()
     }
 }
 
 impl<'a> Deref for UnsizedBytes<'a> {
-    type Target = &'a [u8];
+    type Target = Cow<'a, [u8]>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -209,6 +326,400 @@ impl<'a> DerefMut for UnsizedBytes<'a> {
     }
 }
 
+/// Bytes is a length prefixed slice of raw bytes, such as a skin image or an encryption key. Like `Str`
+/// it borrows the slice from the buffer when decoding from a contiguous slice, but it does not have to
+/// be valid UTF-8.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Bytes<'a, P: Prefix>(Cow<'a, [u8]>, PhantomData<P>);
+
+impl<'a, P: Prefix> Bytes<'a, P> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self(Cow::Borrowed(data), PhantomData)
+    }
+
+    pub fn into_inner(self) -> Cow<'a, [u8]> {
+        self.0
+    }
+}
+
+impl<'a, P: Prefix> Binary<'a> for Bytes<'a, P> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        P::encode(self.0.len(), buf)?;
+
+        buf.write_all(&self.0)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
+        let len = P::decode(buf)?;
+        buf.check_string_len(offset, len)?;
+
+        Ok(Self(buf.read_bytes(len)?, PhantomData))
+    }
+
+    fn encoded_len(&self) -> usize {
+        P::encoded_len(self.0.len()) + self.0.len()
+    }
+}
+
+impl<'a, P: Prefix> Deref for Bytes<'a, P> {
+    type Target = Cow<'a, [u8]>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, P: Prefix> DerefMut for Bytes<'a, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// UnsizedArray decodes elements until the end of the buffer, without any prefix for the length. The same
+/// as `UnsizedBytes`, use this only for an array at the end of the buffer.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct UnsizedArray<B>(Vec<B>);
+
+impl<B> UnsizedArray<B> {
+    pub fn new(array: Vec<B>) -> Self {
+        Self(array)
+    }
+
+    pub fn into_inner(self) -> Vec<B> {
+        self.0
+    }
+}
+
+impl<'a, B: Binary<'a>> Binary<'a> for UnsizedArray<B> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        for element in &self.0 {
+            element.serialize(buf)?;
+        }
+
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
+
+        buf.nested(|buf| {
+            let mut array = Vec::new();
+
+            while buf.remaining() > 0 {
+                buf.check_elements(offset, array.len() + 1)?;
+
+                let position = buf.position();
+                let index = array.len();
+                array.push(B::deserialize(buf).map_err(|err| err.in_element(index))?);
+
+                // An element that takes no bytes would otherwise be decoded forever.
+                if buf.position() == position {
+                    return Err(DecodeError::custom(
+                        buf,
+                        "element of an unsized array did not read any bytes",
+                    ));
+                }
+            }
+
+            Ok(Self(array))
+        })
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.iter().map(B::encoded_len).sum()
+    }
+}
+
+impl<B> Deref for UnsizedArray<B> {
+    type Target = Vec<B>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<B> DerefMut for UnsizedArray<B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Owned counterpart of `Str` backed by a `String`. It does not borrow from the buffer it was decoded
+/// from, so it can be stored or sent to other tasks without re-encoding it.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct OwnedStr<P: Prefix>(String, PhantomData<P>);
+
+impl<P: Prefix> OwnedStr<P> {
+    pub fn new(val: impl Into<String>) -> Self {
+        Self(val.into(), PhantomData)
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl<'a, P: Prefix> Binary<'a> for OwnedStr<P> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        Str::<P>::new(&self.0).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(Str::<P>::deserialize(buf)?.into())
+    }
+
+    fn encoded_len(&self) -> usize {
+        P::encoded_len(self.0.len()) + self.0.len()
+    }
+}
+
+impl<P: Prefix> Deref for OwnedStr<P> {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P: Prefix> DerefMut for OwnedStr<P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a, P: Prefix> From<Str<'a, P>> for OwnedStr<P> {
+    fn from(val: Str<'a, P>) -> Self {
+        Self::new(val.0)
+    }
+}
+
+impl<'a, P: Prefix> From<&'a OwnedStr<P>> for Str<'a, P> {
+    fn from(val: &'a OwnedStr<P>) -> Self {
+        Self::new(val.0.as_str())
+    }
+}
+
+/// Owned counterpart of `Array` backed by a `Vec`. It has no lifetime of its own, so it is `'static`
+/// whenever the elements are.
+#[derive(Clone, PartialEq, Eq)]
+pub struct OwnedArray<B, P: Prefix>(Vec<B>, PhantomData<P>);
+
+impl<B, P: Prefix> OwnedArray<B, P> {
+    pub fn new(array: Vec<B>) -> Self {
+        Self(array, PhantomData)
+    }
+
+    pub fn into_inner(self) -> Vec<B> {
+        self.0
+    }
+}
+
+impl<'a, B: Binary<'a>, P: Prefix> Binary<'a> for OwnedArray<B, P> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        let len = self.0.len();
+        P::encode(len, buf)?;
+
+        for element in &self.0 {
+            element.serialize(buf)?;
+        }
+
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        Ok(Array::<B, P>::deserialize(buf)?.into())
+    }
+
+    fn encoded_len(&self) -> usize {
+        P::encoded_len(self.0.len()) + self.0.iter().map(B::encoded_len).sum::<usize>()
+    }
+}
+
+impl<B, P: Prefix> Deref for OwnedArray<B, P> {
+    type Target = Vec<B>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<B, P: Prefix> DerefMut for OwnedArray<B, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a, B: Binary<'a>, P: Prefix> From<Array<'a, B, P>> for OwnedArray<B, P> {
+    fn from(val: Array<'a, B, P>) -> Self {
+        Self::new(val.0)
+    }
+}
+
+impl<'a, B: Binary<'a>, P: Prefix> From<OwnedArray<B, P>> for Array<'a, B, P> {
+    fn from(val: OwnedArray<B, P>) -> Self {
+        Self::new(val.0)
+    }
+}
+
+/// Owned counterpart of `Bytes` backed by `bytes::Bytes`, which is cheap to clone and to share between
+/// tasks.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct OwnedBytes<P: Prefix>(bytes::Bytes, PhantomData<P>);
+
+impl<P: Prefix> OwnedBytes<P> {
+    pub fn new(data: impl Into<bytes::Bytes>) -> Self {
+        Self(data.into(), PhantomData)
+    }
+
+    pub fn into_inner(self) -> bytes::Bytes {
+        self.0
+    }
+}
+
+impl<'a, P: Prefix> Binary<'a> for OwnedBytes<P> {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        Bytes::<P>::new(&self.0).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let offset = buf.position();
+        let len = P::decode(buf)?;
+        buf.check_string_len(offset, len)?;
+
+        Ok(Self(buf.read_owned(len)?, PhantomData))
+    }
+
+    fn encoded_len(&self) -> usize {
+        P::encoded_len(self.0.len()) + self.0.len()
+    }
+}
+
+impl<P: Prefix> Deref for OwnedBytes<P> {
+    type Target = bytes::Bytes;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P: Prefix> DerefMut for OwnedBytes<P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a, P: Prefix> From<Bytes<'a, P>> for OwnedBytes<P> {
+    fn from(val: Bytes<'a, P>) -> Self {
+        match val.0 {
+            Cow::Borrowed(data) => Self::new(bytes::Bytes::copy_from_slice(data)),
+            Cow::Owned(data) => Self::new(data),
+        }
+    }
+}
+
+impl<'a, P: Prefix> From<&'a OwnedBytes<P>> for Bytes<'a, P> {
+    fn from(val: &'a OwnedBytes<P>) -> Self {
+        Self::new(&val.0)
+    }
+}
+
+/// Owned counterpart of `UnsizedBytes` backed by `Bytes`, which is cheap to clone and to share between
+/// tasks.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct OwnedUnsizedBytes(bytes::Bytes);
+
+impl OwnedUnsizedBytes {
+    pub fn new(data: impl Into<bytes::Bytes>) -> Self {
+        Self(data.into())
+    }
+
+    pub fn into_inner(self) -> bytes::Bytes {
+        self.0
+    }
+}
+
+impl<'a> Binary<'a> for OwnedUnsizedBytes {
+    fn serialize(&self, buf: &mut impl Write) -> Result<(), EncodeError> {
+        UnsizedBytes::new(&self.0).serialize(buf)
+    }
+
+    fn deserialize(buf: &mut impl Reader<'a>) -> Result<Self, DecodeError> {
+        let len = buf.remaining();
+        Ok(Self(buf.read_owned(len)?))
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Deref for OwnedUnsizedBytes {
+    type Target = bytes::Bytes;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for OwnedUnsizedBytes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a> From<UnsizedBytes<'a>> for OwnedUnsizedBytes {
+    fn from(val: UnsizedBytes<'a>) -> Self {
+        match val.0 {
+            Cow::Borrowed(data) => Self::new(bytes::Bytes::copy_from_slice(data)),
+            Cow::Owned(data) => Self::new(data),
+        }
+    }
+}
+
+impl<'a> From<&'a OwnedUnsizedBytes> for UnsizedBytes<'a> {
+    fn from(val: &'a OwnedUnsizedBytes) -> Self {
+        Self::new(&val.0)
+    }
+}
+
 debug_impl_tt!(Str<P: Prefix>);
 debug_impl_tt!(Array<B: Binary<'a>, P: Prefix>);
 debug_impl_tt!(UnsizedBytes);
+debug_impl!(UnsizedArray<B: std::fmt::Debug>);
+debug_impl!(OwnedStr<P: Prefix>);
+debug_impl!(OwnedArray<B: std::fmt::Debug, P: Prefix>);
+debug_impl!(OwnedUnsizedBytes);
+
+/// The amount of bytes shown by the `Debug` output of the byte types before it is truncated.
+const DEBUG_BYTES: usize = 32;
+
+///
+/// This macro implements `Debug` for the byte types, printing the bytes in hex and truncating them after
+/// `DEBUG_BYTES` bytes so that logging a packet with a skin in it does not print megabytes.
+///
+macro_rules! debug_hex_impl {
+    ($([$($generics:tt)*] $ty:ty);+ $(;)?) => {
+        $(
+            impl<$($generics)*> std::fmt::Debug for $ty {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    for byte in self.0.iter().take(DEBUG_BYTES) {
+                        write!(f, "{:02x}", byte)?;
+                    }
+
+                    if self.0.len() > DEBUG_BYTES {
+                        write!(f, "... ({} bytes)", self.0.len())?;
+                    }
+
+                    Ok(())
+                }
+            }
+        )+
+    };
+}
+
+debug_hex_impl!(
+    ['a, P: Prefix] Bytes<'a, P>;
+    [P: Prefix] OwnedBytes<P>;
+);
@@ -0,0 +1,443 @@
+use crate::error::DecodeError;
+use bytes::{Buf, Bytes};
+use std::borrow::Cow;
+
+/// Limits restrict how much a single decode is allowed to allocate and read, so that a malicious
+/// length read straight off the wire cannot make us allocate huge amounts of memory or recurse
+/// forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum amount of elements in a single prefixed array.
+    pub max_elements: usize,
+    /// Maximum length in bytes of a single prefixed string.
+    pub max_string_len: usize,
+    /// Maximum amount of bytes read from the buffer in total.
+    pub max_bytes: usize,
+    /// Maximum nesting depth of arrays, structs and enums.
+    pub max_depth: usize,
+}
+
+impl Limits {
+    /// Limits that never reject anything, to be used for data that we trust.
+    pub const UNLIMITED: Limits = Limits {
+        max_elements: usize::MAX,
+        max_string_len: usize::MAX,
+        max_bytes: usize::MAX,
+        max_depth: usize::MAX,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_elements: 1 << 20,
+            max_string_len: 1 << 20,
+            max_bytes: 1 << 26,
+            max_depth: 64,
+        }
+    }
+}
+
+/// VarIntMode selects how strictly the varint datatypes are decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VarIntMode {
+    /// Accepts over-long encodings and drops the bits of the last byte that do not fit into the type,
+    /// the same way as most implementations of the protocol do.
+    #[default]
+    Lenient,
+    /// Rejects encodings that are longer than needed or that overflow the type, so that every value has
+    /// exactly one encoding.
+    Strict,
+}
+
+///
+/// Reader is implemented for the buffers that all the `Binary` types are decoded from. It carries the
+/// `Limits` of the decode along with the current nesting depth.
+///
+/// `SliceReader` reads from a contiguous slice of bytes so that strings and slices can be borrowed from
+/// it without copying, while `BufReader` reads from any `bytes::Buf`, such as chained or ring buffers,
+/// copying the data that cannot be borrowed.
+///
+pub trait Reader<'a> {
+    /// Returns the limits that this reader enforces.
+    fn limits(&self) -> &Limits;
+
+    /// Returns the offset of the next byte to be read, counted from where the reader started.
+    fn position(&self) -> usize;
+
+    /// Returns the amount of bytes that are left to be read.
+    fn remaining(&self) -> usize;
+
+    /// Reads `len` bytes and advances past them, borrowing them from the underlying data whenever it
+    /// is contiguous. Returns an `UnexpectedEof` error if there are not enough bytes remaining.
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError>;
+
+    /// Reads exactly as many bytes as fit into the destination.
+    fn read_exact(&mut self, dst: &mut [u8]) -> Result<(), DecodeError>;
+
+    /// Returns the current nesting depth, used by `nested` to enforce the depth limit.
+    fn depth_mut(&mut self) -> &mut usize;
+
+    /// Returns the mode that varints are decoded in, unless their type selects one with `Strict`.
+    fn varint_mode(&self) -> VarIntMode {
+        VarIntMode::Lenient
+    }
+
+    /// Returns the bytes that can be read next without copying, which may be fewer than `remaining` for
+    /// non-contiguous buffers. Decoders use it for fast paths and fall back to reading byte by byte when
+    /// it is too short, so readers that cannot lend out their data return an empty slice.
+    fn chunk(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Advances past `len` bytes without reading them.
+    fn advance(&mut self, len: usize) -> Result<(), DecodeError> {
+        self.read_bytes(len)?;
+        Ok(())
+    }
+
+    /// Reads `len` bytes into `Bytes`. Readers over `Bytes` override this so that no copy is made.
+    fn read_owned(&mut self, len: usize) -> Result<Bytes, DecodeError> {
+        Ok(Bytes::copy_from_slice(&self.read_bytes(len)?))
+    }
+
+    /// Reads exactly `N` bytes into an array, used for the fixed width datatypes.
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut array = [0; N];
+        self.read_exact(&mut array)?;
+
+        Ok(array)
+    }
+
+    /// Reads a single byte.
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    /// Checks that the amount of elements read at the offset is within the limits.
+    fn check_elements(&self, offset: usize, len: usize) -> Result<(), DecodeError> {
+        check_limit(offset, len, self.limits().max_elements)
+    }
+
+    /// Checks that the string length read at the offset is within the limits.
+    fn check_string_len(&self, offset: usize, len: usize) -> Result<(), DecodeError> {
+        check_limit(offset, len, self.limits().max_string_len)
+    }
+
+    /// Returns a reader that reads at most `len` more bytes from this one, used to decode a value whose
+    /// length in bytes is known up front.
+    fn take(&mut self, len: usize) -> Take<'_, Self>
+    where
+        Self: Sized,
+    {
+        let end = self.position().saturating_add(len);
+        Take { inner: self, end }
+    }
+
+    /// Decodes a nested value with the provided function, one level deeper than the current depth.
+    /// Returns a `DepthLimit` error if the maximum nesting depth would be exceeded.
+    fn nested<T>(
+        &mut self,
+        decode: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError>
+    where
+        Self: Sized,
+    {
+        let limit = self.limits().max_depth;
+
+        if *self.depth_mut() >= limit {
+            return Err(DecodeError::DepthLimit {
+                offset: self.position(),
+                limit,
+            });
+        }
+
+        *self.depth_mut() += 1;
+        let result = decode(self);
+        *self.depth_mut() -= 1;
+
+        result
+    }
+}
+
+/// SliceReader decodes from a contiguous slice of bytes, lending out borrowed slices of it.
+#[derive(Debug, Clone)]
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    limits: Limits,
+    depth: usize,
+    varint_mode: VarIntMode,
+}
+
+impl<'a> SliceReader<'a> {
+    /// Creates a new reader over the data with the default limits.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_limits(data, Limits::default())
+    }
+
+    /// Creates a new reader over the data with the provided limits.
+    pub fn with_limits(data: &'a [u8], limits: Limits) -> Self {
+        Self {
+            data,
+            pos: 0,
+            limits,
+            depth: 0,
+            varint_mode: VarIntMode::Lenient,
+        }
+    }
+
+    /// Returns the complete underlying slice of data, including the bytes that are already read.
+    pub fn get_ref(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Sets the mode that varints are decoded in.
+    pub fn set_varint_mode(&mut self, mode: VarIntMode) {
+        self.varint_mode = mode;
+    }
+
+    /// Sets the offset of the next byte to be read. Offsets past the end of the data are clamped to it.
+    pub fn set_position(&mut self, pos: usize) {
+        self.pos = pos.min(self.data.len());
+    }
+
+    /// Reads a slice of `len` bytes without copying and advances past it.
+    pub fn read_slice(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        check_read(self, len)?;
+
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+
+        Ok(slice)
+    }
+}
+
+impl<'a> Reader<'a> for SliceReader<'a> {
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError> {
+        Ok(Cow::Borrowed(self.read_slice(len)?))
+    }
+
+    fn read_exact(&mut self, dst: &mut [u8]) -> Result<(), DecodeError> {
+        dst.copy_from_slice(self.read_slice(dst.len())?);
+        Ok(())
+    }
+
+    fn chunk(&self) -> &[u8] {
+        &self.data[self.pos..]
+    }
+
+    fn advance(&mut self, len: usize) -> Result<(), DecodeError> {
+        self.read_slice(len)?;
+        Ok(())
+    }
+
+    fn depth_mut(&mut self) -> &mut usize {
+        &mut self.depth
+    }
+
+    fn varint_mode(&self) -> VarIntMode {
+        self.varint_mode
+    }
+}
+
+/// BufReader decodes from any `bytes::Buf`, including non-contiguous buffers created with `Buf::chain`.
+/// Since the data cannot be borrowed, borrowed types decoded from it own a copy of their bytes.
+#[derive(Debug)]
+pub struct BufReader<B: Buf> {
+    buf: B,
+    pos: usize,
+    limits: Limits,
+    depth: usize,
+    varint_mode: VarIntMode,
+}
+
+impl<B: Buf> BufReader<B> {
+    /// Creates a new reader over the buffer with the default limits.
+    pub fn new(buf: B) -> Self {
+        Self::with_limits(buf, Limits::default())
+    }
+
+    /// Creates a new reader over the buffer with the provided limits.
+    pub fn with_limits(buf: B, limits: Limits) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            limits,
+            depth: 0,
+            varint_mode: VarIntMode::Lenient,
+        }
+    }
+
+    /// Sets the mode that varints are decoded in.
+    pub fn set_varint_mode(&mut self, mode: VarIntMode) {
+        self.varint_mode = mode;
+    }
+
+    /// Returns the underlying buffer, which has been advanced past all the bytes read.
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+impl<'a, B: Buf> Reader<'a> for BufReader<B> {
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.remaining()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError> {
+        check_read(self, len)?;
+
+        let mut data = vec![0; len];
+        self.buf.copy_to_slice(&mut data);
+        self.pos += len;
+
+        Ok(Cow::Owned(data))
+    }
+
+    fn read_exact(&mut self, dst: &mut [u8]) -> Result<(), DecodeError> {
+        check_read(self, dst.len())?;
+
+        self.buf.copy_to_slice(dst);
+        self.pos += dst.len();
+
+        Ok(())
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.buf.chunk()
+    }
+
+    fn advance(&mut self, len: usize) -> Result<(), DecodeError> {
+        check_read(self, len)?;
+
+        self.buf.advance(len);
+        self.pos += len;
+
+        Ok(())
+    }
+
+    fn depth_mut(&mut self) -> &mut usize {
+        &mut self.depth
+    }
+
+    fn varint_mode(&self) -> VarIntMode {
+        self.varint_mode
+    }
+
+    fn read_owned(&mut self, len: usize) -> Result<Bytes, DecodeError> {
+        check_read(self, len)?;
+
+        let data = self.buf.copy_to_bytes(len);
+        self.pos += len;
+
+        Ok(data)
+    }
+}
+
+/// Take is a reader limited to the next bytes of another reader, created with `Reader::take`. Reading
+/// past its end returns an `UnexpectedEof` error even if the other reader has more bytes, and values that
+/// read until the end of the buffer stop at its end.
+#[derive(Debug)]
+pub struct Take<'r, R> {
+    inner: &'r mut R,
+    end: usize,
+}
+
+impl<'a, 'r, R: Reader<'a>> Reader<'a> for Take<'r, R> {
+    fn limits(&self) -> &Limits {
+        self.inner.limits()
+    }
+
+    fn position(&self) -> usize {
+        self.inner.position()
+    }
+
+    fn remaining(&self) -> usize {
+        self.inner
+            .remaining()
+            .min(self.end.saturating_sub(self.inner.position()))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'a, [u8]>, DecodeError> {
+        check_read(self, len)?;
+        self.inner.read_bytes(len)
+    }
+
+    fn read_exact(&mut self, dst: &mut [u8]) -> Result<(), DecodeError> {
+        check_read(self, dst.len())?;
+        self.inner.read_exact(dst)
+    }
+
+    fn chunk(&self) -> &[u8] {
+        let chunk = self.inner.chunk();
+        &chunk[..chunk.len().min(self.remaining())]
+    }
+
+    fn advance(&mut self, len: usize) -> Result<(), DecodeError> {
+        check_read(self, len)?;
+        self.inner.advance(len)
+    }
+
+    fn depth_mut(&mut self) -> &mut usize {
+        self.inner.depth_mut()
+    }
+
+    fn varint_mode(&self) -> VarIntMode {
+        self.inner.varint_mode()
+    }
+
+    fn read_owned(&mut self, len: usize) -> Result<Bytes, DecodeError> {
+        check_read(self, len)?;
+        self.inner.read_owned(len)
+    }
+}
+
+/// Checks that `len` bytes can be read from the reader without running out of data or going over the
+/// total amount of bytes allowed by the limits.
+fn check_read<'a>(reader: &impl Reader<'a>, len: usize) -> Result<(), DecodeError> {
+    if len > reader.remaining() {
+        return Err(DecodeError::eof(reader));
+    }
+
+    let total = reader.position().saturating_add(len);
+    let limit = reader.limits().max_bytes;
+
+    if total > limit {
+        return Err(DecodeError::LengthLimit {
+            offset: reader.position(),
+            len: total,
+            limit,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_limit(offset: usize, len: usize, limit: usize) -> Result<(), DecodeError> {
+    if len > limit {
+        return Err(DecodeError::LengthLimit { offset, len, limit });
+    }
+
+    Ok(())
+}
@@ -1,15 +1,19 @@
-use proc_macro2::{Ident, Span, TokenStream};
-use quote::{quote, ToTokens};
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use std::collections::{HashMap, HashSet};
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{
-    parse2, parse_quote, Data, DeriveInput, Error, Fields, GenericParam, Generics, Lifetime,
-    LifetimeParam, LitInt,
+    parse2, parse_quote, Data, DeriveInput, Error, Expr, ExprLit, ExprUnary, Field, Fields,
+    GenericArgument, GenericParam, Generics, Index, Lifetime, LifetimeParam, Lit, LitInt, Path,
+    PathArguments, Type, UnOp,
 };
-use syn::{Attribute, LitStr, Result, Variant};
+use syn::{Attribute, LitStr, Result, Token, Variant, WherePredicate};
 
 /// Derives the Binary trait on Structs and Enums for serialization and deserialization purposes.
 pub fn binary_derive(item: TokenStream) -> Result<TokenStream> {
-    let mut input = parse2::<DeriveInput>(item.into()).unwrap();
+    let mut input = parse2::<DeriveInput>(item)?;
     let name = input.ident;
 
     if input.generics.lifetimes().count() > 1 {
@@ -28,311 +32,198 @@ pub fn binary_derive(item: TokenStream) -> Result<TokenStream> {
         .map(|l| l.lifetime.clone())
         .unwrap_or_else(|| parse_quote!('a));
 
+    let attrs = ContainerAttrs::parse(&input.attrs)?;
+
     match input.data {
         Data::Struct(struct_) => {
-            let serialize = match &struct_.fields {
-                Fields::Named(fields) => fields
-                    .named
-                    .iter()
-                    .filter_map(|f| {
-                        let name = &f.ident.as_ref().unwrap();
-
-                        for attr in &f.attrs {
-                            if attr.path().is_ident("skip") {
-                                return None;
-                            }
-                        }
-
-                        Some(quote! {
-                            self.#name.serialize(buf);
-                        })
-                    })
-                    .collect(),
-                Fields::Unnamed(fields) => (0..fields.unnamed.len())
-                    .map(|i| {
-                        let lit = LitInt::new(&i.to_string(), Span::call_site());
-
-                        quote! {
-                            self.#lit.serialize(buf);
-                        }
-                    })
-                    .collect(),
-                Fields::Unit => TokenStream::new(),
-            };
-
-            let deserialize = match struct_.fields {
-                Fields::Named(fields) => {
-                    let init = fields.named.iter().map(|f| {
-                        let name = f.ident.as_ref().unwrap();
-
-                        for attr in &f.attrs {
-                            if attr.path().is_ident("skip") {
-                                return quote! {
-                                    #name: Default::default(),
-                                };
-                            }
-                        }
-
-                        quote! {
-                            #name: Binary::deserialize(buf)?,
-                        }
-                    });
-
-                    quote! {
-                        Self {
-                            #(#init)*
-                        }
-                    }
-                }
-                Fields::Unnamed(fields) => {
-                    let init = (0..fields.unnamed.len())
-                        .map(|_| {
-                            quote! {
-                                Binary::deserialize(buf)?,
-                            }
-                        })
-                        .collect::<TokenStream>();
+            if let Some(tag) = attrs.tag {
+                return Err(Error::new_spanned(tag, "`tag` can only be used on enums"));
+            }
 
-                    quote! {
-                        Self(#init)
-                    }
-                }
-                Fields::Unit => quote!(Self),
-            };
+            let FieldsImpl {
+                serialize,
+                encoded_len,
+                deserialize,
+                ..
+            } = fields_impl(&struct_.fields, &name, None, None, &lifetime)?;
 
-            add_trait_bounds(
-                &mut input.generics,
-                quote!(::valence_protocol::__private::Decode<#lifetime>),
-            );
+            add_bounds(&mut input.generics, attrs.bound, &lifetime);
 
             let (impl_generics, ty_generics, where_clause) =
                 decode_split_for_impl(input.generics, lifetime.clone());
 
             Ok(quote! {
-                #[allow(unused_imports)]
+                #[allow(unused_imports, clippy::needless_question_mark)]
                 impl #impl_generics ::binary::Binary<#lifetime> for #name #ty_generics
                 #where_clause
                 {
-                    fn serialize(&self, buf: &mut impl Write) {
+                    fn serialize(&self, __binary_buf: &mut impl Write) -> std::result::Result<(), ::binary::error::EncodeError> {
                         use bytes::BytesMut;
                         use ::binary::Binary;
 
                         #serialize
+                        Ok(())
                     }
 
-                    fn deserialize(buf: &mut std::io::Cursor<&'a [u8]>) -> std::io::Result<Self> {
+                    fn deserialize(__binary_buf: &mut impl ::binary::reader::Reader<#lifetime>) -> std::result::Result<Self, ::binary::error::DecodeError> {
                         use bytes::BytesMut;
                         use ::binary::Binary;
+                        use ::binary::reader::Reader;
 
-                        Ok(#deserialize)
+                        __binary_buf.nested(|__binary_buf| Ok(#deserialize))
+                    }
+
+                    fn encoded_len(&self) -> usize {
+                        use ::binary::Binary;
+
+                        0 #encoded_len
                     }
                 }
             })
         }
         Data::Enum(enum_) => {
-            let mut datatype = None;
+            let tag = attrs
+                .tag
+                .unwrap_or_else(|| quote!(::binary::datatypes::VarI32));
 
-            for attr in &input.attrs {
-                if attr.path().is_ident("data") {
-                    let _ = attr.parse_nested_meta(|meta| {
-                        if meta.path.is_ident("datatype") {
-                            let value = meta.value()?;
-                            let v: LitStr = value.parse()?;
+            let (variants, other) = pair_variants_with_discriminants(enum_.variants)?;
 
-                            datatype = Some(v.value());
-                        }
+            let mut serialize = TokenStream::new();
+            let mut encoded_len = TokenStream::new();
+            let mut deserialize = TokenStream::new();
+            let mut checks = TokenStream::new();
 
-                        Ok(())
-                    });
-                }
-            }
+            for (disc, variant) in &variants {
+                let variant_name = &variant.ident;
+                let FieldsImpl {
+                    pattern,
+                    serialize: write,
+                    encoded_len: len,
+                    deserialize: read,
+                } = fields_impl(&variant.fields, &name, Some(variant_name), None, &lifetime)?;
 
-            let datatype = match datatype {
-                Some(val) => val,
-                None => String::from("VarI32"),
-            };
+                // Discriminants that do not fit into the tag are rejected at compile time, so that the
+                // conversion below cannot fail.
+                let message = format!(
+                    "discriminant {} of `{}::{}` does not fit into `{}`",
+                    disc,
+                    name,
+                    variant_name,
+                    tag.to_string().replace(' ', "")
+                );
+                checks.extend(quote! {
+                    assert!(
+                        #disc >= <#tag as ::binary::encoding::Discriminant>::MIN
+                            && #disc <= <#tag as ::binary::encoding::Discriminant>::MAX,
+                        #message
+                    );
+                });
 
-            let datatypes = [
-                "I8", "U8", "I16", "U16", "I32", "U32", "I16BE", "U16BE", "I32BE", "U32BE",
-                "VarI32", "VarU32",
-            ];
+                let tag_value = quote! {
+                    <#tag as ::binary::encoding::Discriminant>::from_discriminant(#disc).unwrap()
+                };
 
-            if !datatypes.contains(&datatype.as_str()) {
-                return Ok(quote! {
-                    compile_error!("Datatypes can only be of type I8, U8, I16, U16, I32, U32, I16BE, U16BE, I32BE, U32BE, VarI32, VarU32");
+                serialize.extend(quote! {
+                    #pattern => {
+                        #tag_value.serialize(__binary_buf)?;
+                        #write
+                    }
+                });
+                encoded_len.extend(quote! {
+                    #pattern => #tag_value.encoded_len() #len,
+                });
+                deserialize.extend(quote! {
+                    #disc => Ok(#read),
                 });
             }
 
-            let variants = pair_variants_with_discriminants(enum_.variants)?;
+            // Unknown discriminants are stored in the `other` variant if there is one, or are an error. Its
+            // first field is the tag, so it is serialized without writing a discriminant.
+            let (offset, fallback) = match &other {
+                Some(variant) => {
+                    let FieldsImpl {
+                        pattern,
+                        serialize: write,
+                        encoded_len: len,
+                        deserialize: read,
+                    } = fields_impl(
+                        &variant.fields,
+                        &name,
+                        Some(&variant.ident),
+                        Some(quote!(__binary_tag)),
+                        &lifetime,
+                    )?;
 
-            let serialize = variants
-                .iter()
-                .map(|(disc, variant)| {
-                    let variant_name = &variant.ident;
-
-                    let encode_disc = quote! {
-                        match #datatype {
-                            "I8" => I8::new(#disc as i8).serialize(buf),
-                            "U8" => U8::new(#disc as u8).serialize(buf),
-                            "I16" => I16::<LE>::new(#disc as i16).serialize(buf),
-                            "U16" => U16::<LE>::new(#disc as u16).serialize(buf),
-                            "I32" => I32::<LE>::new(#disc as i32).serialize(buf),
-                            "U32" => U32::<LE>::new(#disc as u32).serialize(buf),
-                            "I16BE" => I16::<BE>::new(#disc as i16).serialize(buf),
-                            "U16BE" => U16::<BE>::new(#disc as u16).serialize(buf),
-                            "I32BE" => I32::<BE>::new(#disc as i32).serialize(buf),
-                            "U32BE" => U32::<BE>::new(#disc as u32).serialize(buf),
-                            "VarI32" => VarI32::new(#disc as i32).serialize(buf),
-                            "VarU32" => VarU32::new(#disc as u32).serialize(buf),
-                            _ => panic!("Unable to find the datatype by the name {:?}", #datatype)
+                    serialize.extend(quote! {
+                        #pattern => {
+                            #write
                         }
-                    };
-
-                    match &variant.fields {
-                        Fields::Named(fields) => {
-                            let field_names = fields
-                                .named
-                                .iter()
-                                .map(|f| f.ident.as_ref().unwrap())
-                                .collect::<Vec<_>>();
-
-                            let encode_fields = field_names
-                                .iter()
-                                .map(|name| {
-                                    quote! {
-                                        #name.serialize(buf);
-                                    }
-                                })
-                                .collect::<TokenStream>();
-
-                            quote! {
-                                Self::#variant_name { #(#field_names,)* } => {
-                                    #encode_disc
-                                    #encode_fields
-                                }
-                            }
-                        }
-                        Fields::Unnamed(fields) => {
-                            let field_names = (0..fields.unnamed.len())
-                                .map(|i| Ident::new(&format!("_{i}"), Span::call_site()))
-                                .collect::<Vec<_>>();
-
-                            let encode_fields = field_names
-                                .iter()
-                                .map(|name| {
-                                    quote! {
-                                        #name.serialize(buf);
-                                    }
-                                })
-                                .collect::<TokenStream>();
-
-                            quote! {
-                                Self::#variant_name(#(#field_names,)*) => {
-                                    #encode_disc
-                                    #encode_fields
-                                }
-                            }
-                        }
-                        Fields::Unit => {
-                            quote! {
-                                 Self::#variant_name => #encode_disc,
-                            }
-                        }
-                    }
-                })
-                .collect::<TokenStream>();
+                    });
+                    encoded_len.extend(quote! {
+                        #pattern => 0 #len,
+                    });
 
-            let deserialize = variants
-                .iter()
-                .map(|(disc, variant)| {
-                    let variant_name = &variant.ident;
-
-                    match &variant.fields {
-                        Fields::Named(fields) => {
-                            let fields = fields
-                                .named
-                                .iter()
-                                .map(|f| {
-                                    let field = f.ident.as_ref().unwrap();
-
-                                    quote! {
-                                        #field: Binary::deserialize(buf)?,
-                                    }
-                                })
-                                .collect::<TokenStream>();
-
-                            quote! {
-                                #disc => Ok(Self::#variant_name { #fields }),
-                            }
-                        }
-                        Fields::Unnamed(fields) => {
-                            let fields = (0..fields.unnamed.len())
-                                .map(|_| {
-                                    quote! {
-                                        Binary::deserialize(buf)?,
-                                    }
-                                })
-                                .collect::<TokenStream>();
-
-                            quote! {
-                                #disc => Ok(Self::#variant_name(#fields)),
-                            }
-                        }
-                        Fields::Unit => quote!(#disc => Ok(Self::#variant_name),),
-                    }
-                })
-                .collect::<TokenStream>();
+                    (TokenStream::new(), quote!(_ => Ok(#read),))
+                }
+                None => (
+                    quote!(let __binary_offset = __binary_buf.position();),
+                    quote! {
+                        __binary_value => Err(::binary::error::DecodeError::InvalidDiscriminant {
+                            offset: __binary_offset,
+                            value: __binary_value,
+                        }),
+                    },
+                ),
+            };
 
-            add_trait_bounds(
-                &mut input.generics,
-                quote!(::valence_protocol::__private::Decode<#lifetime>),
-            );
+            add_bounds(&mut input.generics, attrs.bound, &lifetime);
 
             let (impl_generics, ty_generics, where_clause) =
                 decode_split_for_impl(input.generics, lifetime.clone());
 
             Ok(quote! {
-                 #[allow(unused_imports)]
+                const _: () = {
+                    #checks
+                };
+
+                #[allow(unused_imports, clippy::needless_question_mark)]
                 impl #impl_generics ::binary::Binary<#lifetime> for #name #ty_generics
                 #where_clause
                 {
-                    fn serialize(&self, buf: &mut impl Write) {
+                    fn serialize(&self, __binary_buf: &mut impl Write) -> std::result::Result<(), ::binary::error::EncodeError> {
                         use bytes::BytesMut;
                         use ::binary::Binary;
-                        use ::binary::datatypes::{I8, U8, I16, U16, I32, U32, VarI32, VarU32};
-                        use byteorder::{BE, LE};
 
                         match self {
                             #serialize
                             _ => unreachable!(),
                         }
+
+                        Ok(())
                     }
 
-                    fn deserialize(buf: &mut std::io::Cursor<&'a [u8]>) -> std::io::Result<Self> {
+                    fn deserialize(__binary_buf: &mut impl ::binary::reader::Reader<#lifetime>) -> std::result::Result<Self, ::binary::error::DecodeError> {
                         use bytes::BytesMut;
                         use ::binary::Binary;
-                        use ::binary::datatypes::{I8, U8, I16, U16, I32, U32, VarI32, VarU32};
-                        use byteorder::{BE, LE};
-
-                        let disc = match #datatype {
-                            "I8" => I8::deserialize(buf)?.0 as usize,
-                            "U8" => U8::deserialize(buf)?.0 as usize,
-                            "U16" => U16::<LE>::deserialize(buf)?.0 as usize,
-                            "I16" => I16::<LE>::deserialize(buf)?.0 as usize,
-                            "I32" => I32::<LE>::deserialize(buf)?.0 as usize,
-                            "U32" => U32::<LE>::deserialize(buf)?.0 as usize,
-                            "U16BE" => U16::<BE>::deserialize(buf)?.0 as usize,
-                            "I16BE" => I16::<BE>::deserialize(buf)?.0 as usize,
-                            "I32BE" => I32::<BE>::deserialize(buf)?.0 as usize,
-                            "U32BE" => U32::<BE>::deserialize(buf)?.0 as usize,
-                            "VarI32" => VarI32::deserialize(buf)?.0 as usize,
-                            "VarU32" => VarU32::deserialize(buf)?.0 as usize,
-                            _ => panic!("Unable to find the datatype by the name {:?}", #datatype)
-                        };
-
-                        match disc {
-                            #deserialize
-                            n => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Unexpected enum discriminant {:?}", n)))
+                        use ::binary::reader::Reader;
+
+                        __binary_buf.nested(|__binary_buf| {
+                            #offset
+                            let __binary_tag = <#tag as ::binary::Binary<#lifetime>>::deserialize(__binary_buf)?;
+
+                            match <#tag as ::binary::encoding::Discriminant>::to_discriminant(&__binary_tag) {
+                                #deserialize
+                                #fallback
+                            }
+                        })
+                    }
+
+                    fn encoded_len(&self) -> usize {
+                        use ::binary::Binary;
+
+                        match self {
+                            #encoded_len
+                            _ => unreachable!(),
                         }
                     }
                 }
@@ -345,36 +236,757 @@ pub fn binary_derive(item: TokenStream) -> Result<TokenStream> {
     }
 }
 
+/// The variants of an enum paired with their discriminants.
+type DiscriminantVariants = Vec<(i128, Variant)>;
+
 /// Pairs the variants from the Iterator passed into a Vector of a tuple of the discriminant
-/// and the variant.
+/// and the variant, and returns the `#[binary(other)]` variant separately.
+///
+/// The discriminant is the one of the `#[variant(tag = N)]` attribute or the Rust discriminant of the
+/// variant if it has either, and the one after the previous variant otherwise, the same way as in Rust.
 fn pair_variants_with_discriminants(
     variants: impl IntoIterator<Item = Variant>,
-) -> Result<Vec<(usize, Variant)>> {
+) -> Result<(DiscriminantVariants, Option<Variant>)> {
     let mut discriminant = 0;
-    variants
-        .into_iter()
-        .map(|v| {
-            if let Some(i) = parse_tag_attr(&v.attrs)? {
-                discriminant = i;
+    let mut pairs: Vec<(i128, Variant)> = Vec::new();
+    let mut other = None;
+
+    for v in variants {
+        let tag = parse_tag_attr(&v.attrs)?;
+
+        if parse_other_attr(&v.attrs)? {
+            if other.is_some() {
+                return Err(Error::new_spanned(
+                    &v.ident,
+                    "only one variant can be `other`",
+                ));
+            }
+            if tag.is_some() || v.discriminant.is_some() {
+                return Err(Error::new_spanned(
+                    &v.ident,
+                    "the `other` variant cannot have a discriminant",
+                ));
+            }
+            if !matches!(v.fields.len(), 1 | 2) {
+                return Err(Error::new_spanned(
+                    &v.ident,
+                    "the `other` variant must have a field for the tag, optionally followed by one for the rest of the data",
+                ));
             }
 
-            let pair = (discriminant, v);
             discriminant += 1;
+            other = Some(v);
+            continue;
+        }
+
+        match (tag, &v.discriminant) {
+            (Some(_), Some((_, expr))) => {
+                return Err(Error::new_spanned(
+                    expr,
+                    "variant cannot have both a discriminant and a `tag` attribute",
+                ))
+            }
+            (Some(i), None) => discriminant = i,
+            (None, Some((_, expr))) => discriminant = eval_discriminant(expr)?,
+            (None, None) => {}
+        }
+
+        if let Some((_, prev)) = pairs.iter().find(|(disc, _)| *disc == discriminant) {
+            return Err(Error::new_spanned(
+                &v.ident,
+                format!(
+                    "discriminant {} is already used by `{}`",
+                    discriminant, prev.ident
+                ),
+            ));
+        }
+
+        pairs.push((discriminant, v));
+        discriminant += 1;
+    }
 
-            Ok(pair)
+    Ok((pairs, other))
+}
+
+/// Evaluates the Rust discriminant of a variant, which has to be an integer literal.
+fn eval_discriminant(expr: &Expr) -> Result<i128> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => lit.base10_parse(),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => Ok(-eval_discriminant(expr)?),
+        Expr::Group(group) => eval_discriminant(&group.expr),
+        Expr::Paren(paren) => eval_discriminant(&paren.expr),
+        _ => Err(Error::new_spanned(
+            expr,
+            "discriminant must be an integer literal to derive `Binary`",
+        )),
+    }
+}
+
+/// The code generated for the fields of a struct or of an enum variant.
+struct FieldsImpl {
+    /// The pattern that binds the fields of an enum variant, such as `Self::Move { x, y }`.
+    pattern: TokenStream,
+    /// The statements that serialize the fields.
+    serialize: TokenStream,
+    /// The terms that add up to the encoded length of the fields, each starting with `+`.
+    encoded_len: TokenStream,
+    /// The expression that deserializes the fields and constructs the struct or variant.
+    deserialize: TokenStream,
+}
+
+/// Generates the code for the fields of the struct, or of the enum variant if `variant` is set, so that
+/// the field attributes work the same on named fields, tuple fields and the fields of enum variants.
+///
+/// Fields are deserialized into local variables named after them, or `_0`, `_1`, etc. for tuple fields,
+/// so that the `count`, `len_bytes` and `if` attributes can refer to the fields decoded before them. The
+/// locals of the generated code itself, including the reader and the writer, are prefixed with
+/// `__binary_` so that they are not shadowed by the fields or by what the attributes refer to. If
+/// `first` is set, the first field is not decoded but initialized with it, which is used for the tag of
+/// the `other` variant.
+///
+/// Errors that occur while decoding a field are wrapped with the name of the type and the field, so that
+/// they lead to the value that failed to decode.
+fn fields_impl(
+    fields: &Fields,
+    type_name: &Ident,
+    variant: Option<&Ident>,
+    first: Option<TokenStream>,
+    lifetime: &Lifetime,
+) -> Result<FieldsImpl> {
+    let kind = fields;
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let name = f.ident.clone().unwrap_or_else(|| format_ident!("_{}", i));
+            // The fields of a variant are accessed through the references bound by the match arm.
+            let access = match (variant, &f.ident) {
+                (Some(_), _) => quote!((*#name)),
+                (None, Some(ident)) => quote!(self.#ident),
+                (None, None) => {
+                    let index = Index::from(i);
+                    quote!(self.#index)
+                }
+            };
+
+            Ok((name, access, &f.ty, FieldAttrs::parse(f)?))
         })
-        .collect::<Result<_>>()
+        .collect::<Result<Vec<_>>>()?;
+
+    // The lengths that the fields referenced by `count` and `len_bytes` are written from, keyed by the
+    // name of the referenced field.
+    let mut counts = HashMap::new();
+    let mut checks = TokenStream::new();
+
+    for (i, (name, access, ty, attrs)) in fields.iter().enumerate() {
+        let (count, len) = match (&attrs.count, attrs.len_bytes_field()) {
+            (Some(count), _) => (count, quote!(#access.len())),
+            (None, Some(count)) => (count, attrs.encoded_len(access.clone(), ty, lifetime)),
+            (None, None) => continue,
+        };
+
+        if !fields[..i].iter().any(|(name, ..)| name == count) {
+            return Err(Error::new(
+                count.span(),
+                format!("`{}` must be a field declared before `{}`", count, name),
+            ));
+        }
+
+        match counts.get(&count.to_string()) {
+            Some((other, other_len)) => {
+                let message = format!("`{}` and `{}` must have the same length", other, name);
+
+                checks.extend(quote! {
+                    if #len != #other_len {
+                        return Err(::binary::error::EncodeError::Custom(String::from(#message)));
+                    }
+                });
+            }
+            None => {
+                counts.insert(count.to_string(), (name, len));
+            }
+        }
+    }
+
+    let mut serialize = checks;
+    let mut encoded_len = TokenStream::new();
+    let mut deserialize = TokenStream::new();
+
+    for (i, (name, access, ty, attrs)) in fields.iter().enumerate() {
+        if let Some(first) = first.as_ref().filter(|_| i == 0) {
+            deserialize.extend(quote! {
+                let #name: #ty = #first;
+            });
+        } else if attrs.skip {
+            deserialize.extend(quote! {
+                let #name: #ty = Default::default();
+            });
+            continue;
+        }
+
+        if let Some((_, len)) = counts.get(&name.to_string()) {
+            let write = attrs.serialize(quote!(__binary_count), ty, lifetime);
+            let count_len = attrs.encoded_len(quote!(__binary_count), ty, lifetime);
+
+            serialize.extend(quote! {
+                {
+                    let __binary_len = #len;
+                    let __binary_count = <#ty as ::binary::encoding::Count>::from_count(__binary_len).ok_or(
+                        ::binary::error::EncodeError::LengthOverflow {
+                            len: __binary_len,
+                            max: <#ty as ::binary::encoding::Count>::MAX,
+                        },
+                    )?;
+                    #write
+                }
+            });
+            encoded_len.extend(quote! {
+                + match <#ty as ::binary::encoding::Count>::from_count(#len) {
+                    Some(__binary_count) => #count_len,
+                    None => 0,
+                }
+            });
+        } else if let Some(condition) = &attrs.condition {
+            let value_ty = option_value(ty).ok_or_else(|| {
+                Error::new(condition.span(), "`if` can only be used on `Option` fields")
+            })?;
+            let write = attrs.serialize(quote!((*__binary_val)), value_ty, lifetime);
+            let len = attrs.encoded_len(quote!((*__binary_val)), value_ty, lifetime);
+
+            // The condition is evaluated on references to the values that the earlier fields are decoded
+            // as, so that the value is written exactly when it is read back.
+            let referenced = referenced_idents(condition.to_token_stream());
+
+            let bindings = fields[..i]
+                .iter()
+                .filter(|(name, ..)| referenced.contains(&name.to_string()))
+                .map(|(name, access, ty, attrs)| {
+                    if let Some((_, len)) = counts.get(&name.to_string()) {
+                        // The count is written before, so it fits into the field.
+                        quote! {
+                            let #name: &#ty = &<#ty as ::binary::encoding::Count>::from_count(#len).unwrap();
+                        }
+                    } else if attrs.skip {
+                        quote!(let #name: &#ty = &Default::default();)
+                    } else {
+                        quote!(let #name: &#ty = &#access;)
+                    }
+                });
+            let message = format!(
+                "`{}` must be `Some` exactly when `{}` is true",
+                field_label(kind, name, i),
+                condition.to_token_stream()
+            );
+
+            serialize.extend(quote! {
+                {
+                    let __binary_condition: bool = {
+                        #(#bindings)*
+                        #condition
+                    };
+
+                    match (__binary_condition, &#access) {
+                        (true, Some(__binary_val)) => {
+                            #write
+                        }
+                        (false, None) => {}
+                        _ => return Err(::binary::error::EncodeError::Custom(String::from(#message))),
+                    }
+                }
+            });
+            encoded_len.extend(quote! {
+                + match &#access {
+                    Some(__binary_val) => #len,
+                    None => 0,
+                }
+            });
+        } else if let Some(element_ty) = attrs.count.as_ref().and_then(|_| vec_element(ty)) {
+            let element = attrs.element_attrs();
+            let write = element.serialize(quote!((*__binary_element)), element_ty, lifetime);
+            let element_len =
+                element.encoded_len(quote!((*__binary_element)), element_ty, lifetime);
+
+            serialize.extend(quote! {
+                for __binary_element in &#access {
+                    #write
+                }
+            });
+            encoded_len.extend(quote! {
+                + #access.iter().map(|__binary_element| #element_len).sum::<usize>()
+            });
+        } else {
+            let write = attrs.serialize(access.clone(), ty, lifetime);
+            let len = attrs.encoded_len(access.clone(), ty, lifetime);
+
+            serialize.extend(write);
+            encoded_len.extend(quote!(+ #len));
+        }
+
+        if i == 0 && first.is_some() {
+            continue;
+        }
+
+        let read = if let Some(count) = &attrs.count {
+            let element_ty = vec_element(ty)
+                .ok_or_else(|| Error::new(ty.span(), "`count` can only be used on `Vec` fields"))?;
+            let element = attrs.element_attrs().deserialize(element_ty, lifetime);
+            let message = format!("`{}` is not a valid count", count);
+
+            quote! {
+                {
+                    let __binary_len = ::binary::encoding::Count::to_count(&#count);
+                    let __binary_offset = __binary_buf.position();
+                    let __binary_len = __binary_len.ok_or_else(|| {
+                        ::binary::error::DecodeError::Custom {
+                            offset: __binary_offset,
+                            message: String::from(#message),
+                        }
+                    })?;
+                    __binary_buf.check_elements(__binary_offset, __binary_len)?;
+
+                    __binary_buf.nested(|__binary_buf| {
+                        let mut __binary_array =
+                            Vec::with_capacity(__binary_len.min(__binary_buf.remaining()));
+
+                        for __binary_index in 0..__binary_len {
+                            __binary_array.push(
+                                (|| -> std::result::Result<#element_ty, ::binary::error::DecodeError> {
+                                    Ok(#element)
+                                })()
+                                .map_err(|__binary_err| __binary_err.in_element(__binary_index))?,
+                            );
+                        }
+
+                        Ok(__binary_array)
+                    })?
+                }
+            }
+        } else if let Some(condition) = &attrs.condition {
+            let read = attrs.deserialize(option_value(ty).unwrap(), lifetime);
+            let referenced = referenced_idents(condition.to_token_stream());
+            let bindings = fields[..i]
+                .iter()
+                .map(|(name, ..)| name)
+                .filter(|name| referenced.contains(&name.to_string()));
+
+            quote! {
+                if {
+                    #(let #bindings = &#bindings;)*
+                    #condition
+                } {
+                    Some(#read)
+                } else {
+                    None
+                }
+            }
+        } else if let Some(len_bytes) = &attrs.len_bytes {
+            let read = attrs.deserialize(ty, lifetime);
+            let message = format!("`{}` is not a valid length", len_bytes.to_token_stream());
+
+            quote! {
+                {
+                    let __binary_len = ::binary::encoding::Count::to_count(&(#len_bytes));
+                    let __binary_offset = __binary_buf.position();
+                    let __binary_len = __binary_len.ok_or_else(|| ::binary::error::DecodeError::Custom {
+                        offset: __binary_offset,
+                        message: String::from(#message),
+                    })?;
+
+                    if __binary_len > __binary_buf.remaining() {
+                        return Err(::binary::error::DecodeError::eof(__binary_buf));
+                    }
+
+                    let __binary_buf = &mut __binary_buf.take(__binary_len);
+                    let __binary_val = #read;
+
+                    if __binary_buf.remaining() > 0 {
+                        return Err(::binary::error::DecodeError::TrailingBytes {
+                            offset: __binary_buf.position(),
+                            remaining: __binary_buf.remaining(),
+                        });
+                    }
+
+                    __binary_val
+                }
+            }
+        } else {
+            attrs.deserialize(ty, lifetime)
+        };
+
+        let field = field_label(kind, name, i);
+        let type_name = type_name.to_string();
+        let context = match variant {
+            Some(variant) => {
+                let variant = variant.to_string();
+                quote!(__binary_err.in_variant(#type_name, #variant, #field))
+            }
+            None => quote!(__binary_err.in_field(#type_name, #field)),
+        };
+
+        deserialize.extend(quote! {
+            let #name: #ty = (|| -> std::result::Result<#ty, ::binary::error::DecodeError> {
+                Ok(#read)
+            })()
+            .map_err(|__binary_err| #context)?;
+        });
+    }
+
+    let path = match variant {
+        Some(variant) => quote!(Self::#variant),
+        None => quote!(Self),
+    };
+    let names = fields.iter().map(|(name, ..)| name).collect::<Vec<_>>();
+    // Fields that are not serialized from their binding, such as skipped fields and the fields that hold
+    // a count, are not bound so that they do not cause unused variable warnings.
+    let unused = |(name, _, _, attrs): &(Ident, _, _, FieldAttrs)| {
+        attrs.skip || counts.contains_key(&name.to_string())
+    };
+
+    let (pattern, construct) = match kind {
+        Fields::Named(_) => {
+            let bindings = fields.iter().map(|field| {
+                let name = &field.0;
+
+                if unused(field) {
+                    quote!(#name: _)
+                } else {
+                    quote!(#name)
+                }
+            });
+
+            (
+                quote!(#path { #(#bindings),* }),
+                quote!(#path { #(#names),* }),
+            )
+        }
+        Fields::Unnamed(_) => {
+            let bindings = fields.iter().map(|field| {
+                let name = &field.0;
+
+                if unused(field) {
+                    quote!(_)
+                } else {
+                    quote!(#name)
+                }
+            });
+
+            (quote!(#path(#(#bindings),*)), quote!(#path(#(#names),*)))
+        }
+        Fields::Unit => (path.clone(), path),
+    };
+
+    Ok(FieldsImpl {
+        pattern,
+        serialize,
+        encoded_len,
+        deserialize: quote! {
+            {
+                #deserialize
+                #construct
+            }
+        },
+    })
+}
+
+/// Returns the name of a field as it is written in errors. Tuple fields are named by their index.
+fn field_label(kind: &Fields, name: &Ident, index: usize) -> String {
+    match kind {
+        Fields::Named(_) => name.unraw().to_string(),
+        _ => index.to_string(),
+    }
+}
+
+/// Returns the identifiers that an expression refers to, leaving out the ones after a `.`, which are
+/// the fields and methods of other values.
+fn referenced_idents(tokens: TokenStream) -> HashSet<String> {
+    let mut idents = HashSet::new();
+    let mut after_dot = false;
+
+    for token in tokens {
+        match &token {
+            TokenTree::Ident(ident) if !after_dot => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => idents.extend(referenced_idents(group.stream())),
+            _ => {}
+        }
+
+        after_dot = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '.');
+    }
+
+    idents
+}
+
+/// Returns the type of the elements if the type is a `Vec`.
+fn vec_element(ty: &Type) -> Option<&Type> {
+    generic_argument(ty, "Vec")
+}
+
+/// Returns the type of the value if the type is an `Option`.
+fn option_value(ty: &Type) -> Option<&Type> {
+    generic_argument(ty, "Option")
+}
+
+/// Returns the first generic argument of the type if its name is `name`.
+fn generic_argument<'t>(ty: &'t Type, name: &str) -> Option<&'t Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+
+    if segment.ident != name {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Attributes of a field that change how the field is encoded.
+#[derive(Default)]
+struct FieldAttrs {
+    /// Set with `#[skip]`, the field is not encoded and is decoded as its default value.
+    skip: bool,
+    /// The `Encoding` selected with `#[binary(le)]`, `#[binary(be)]` or `#[binary(varint)]`, for fields
+    /// that are not `Binary` themselves or for their elements when combined with `prefix` or `count`.
+    element: Option<TokenStream>,
+    /// The length prefix selected with `#[binary(prefix = "...")]`.
+    prefix: Option<TokenStream>,
+    /// Set with `#[binary(count = "field")]`, the `Vec` is encoded without a prefix and the amount of
+    /// elements is held by the earlier field.
+    count: Option<Ident>,
+    /// Set with `#[binary(len_bytes = "expr")]`, the field is decoded from exactly as many bytes as the
+    /// expression, which can refer to the earlier fields, evaluates to.
+    len_bytes: Option<Expr>,
+    /// Set with `#[binary(if = "expr")]` on an `Option`, the value is encoded without the `bool` that
+    /// `Option` is prefixed with, and decoded only when the expression, which refers to the earlier
+    /// fields by reference, is true. Serializing fails if the value is `None` when the expression is true, or `Some`
+    /// when it is false.
+    condition: Option<Expr>,
+    /// Set with `#[binary(serialize_with = "path")]` or `#[binary(with = "module")]`, the function that
+    /// serializes the value instead of `Binary`, called as `path(&val, buf)`.
+    serialize_with: Option<TokenStream>,
+    /// Set with `#[binary(deserialize_with = "path")]` or `#[binary(with = "module")]`, the function that
+    /// deserializes the value instead of `Binary`, called as `path(buf)`.
+    deserialize_with: Option<TokenStream>,
+}
+
+impl FieldAttrs {
+    /// Parses the `skip` and `binary` attributes of a field.
+    fn parse(field: &Field) -> Result<Self> {
+        let mut res = Self::default();
+
+        for attr in &field.attrs {
+            if attr.path().is_ident("skip") {
+                res.skip = true;
+            } else if attr.path().is_ident("binary") {
+                attr.parse_nested_meta(|meta| {
+                    let encoding = if meta.path.is_ident("le") {
+                        quote!(::binary::encoding::LE)
+                    } else if meta.path.is_ident("be") {
+                        quote!(::binary::encoding::BE)
+                    } else if meta.path.is_ident("varint") {
+                        quote!(::binary::encoding::VarInt)
+                    } else if meta.path.is_ident("prefix") {
+                        let value = meta.value()?.parse::<LitStr>()?;
+                        res.prefix = Some(datatype_type(&value)?);
+                        return Ok(());
+                    } else if meta.path.is_ident("count") {
+                        let value = meta.value()?.parse::<LitStr>()?;
+                        res.count = Some(value.parse()?);
+                        return Ok(());
+                    } else if meta.path.is_ident("len_bytes") {
+                        let value = meta.value()?.parse::<LitStr>()?;
+                        res.len_bytes = Some(value.parse()?);
+                        return Ok(());
+                    } else if meta.path.is_ident("if") {
+                        let value = meta.value()?.parse::<LitStr>()?;
+                        res.condition = Some(value.parse()?);
+                        return Ok(());
+                    } else if meta.path.is_ident("with") {
+                        let module = meta.value()?.parse::<LitStr>()?.parse::<Path>()?;
+                        res.serialize_with = Some(quote!(#module::serialize));
+                        res.deserialize_with = Some(quote!(#module::deserialize));
+                        return Ok(());
+                    } else if meta.path.is_ident("serialize_with") {
+                        let path = meta.value()?.parse::<LitStr>()?.parse::<Path>()?;
+                        res.serialize_with = Some(path.to_token_stream());
+                        return Ok(());
+                    } else if meta.path.is_ident("deserialize_with") {
+                        let path = meta.value()?.parse::<LitStr>()?.parse::<Path>()?;
+                        res.deserialize_with = Some(path.to_token_stream());
+                        return Ok(());
+                    } else {
+                        return Err(meta.error("unrecognized argument"));
+                    };
+
+                    if res.element.replace(encoding).is_some() {
+                        return Err(meta.error("only one of `le`, `be` and `varint` can be used"));
+                    }
+
+                    Ok(())
+                })?;
+            }
+        }
+
+        if res.count.is_some() && (res.prefix.is_some() || res.len_bytes.is_some()) {
+            return Err(Error::new(
+                field.span(),
+                "`count` cannot be used together with `prefix` or `len_bytes`",
+            ));
+        }
+
+        if (res.serialize_with.is_some() || res.deserialize_with.is_some())
+            && (res.element.is_some() || res.prefix.is_some())
+        {
+            return Err(Error::new(
+                field.span(),
+                "custom functions cannot be used together with `le`, `be`, `varint` or `prefix`",
+            ));
+        }
+
+        if res.condition.is_some() && (res.count.is_some() || res.len_bytes.is_some()) {
+            return Err(Error::new(
+                field.span(),
+                "`if` cannot be used together with `count` or `len_bytes`",
+            ));
+        }
+
+        Ok(res)
+    }
+
+    /// Returns the `Encoding` of the field, if it has one.
+    fn encoding(&self) -> Option<TokenStream> {
+        match (&self.prefix, &self.element) {
+            (Some(prefix), Some(element)) => {
+                Some(quote!(::binary::encoding::Prefixed<#prefix, #element>))
+            }
+            (prefix, element) => prefix.clone().or(element.clone()),
+        }
+    }
+
+    /// Returns the attributes that the elements of a `count` field are encoded with, which include the
+    /// custom functions.
+    fn element_attrs(&self) -> Self {
+        Self {
+            element: self.element.clone(),
+            serialize_with: self.serialize_with.clone(),
+            deserialize_with: self.deserialize_with.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the field that holds the length in bytes, if `len_bytes` is just the name of a field.
+    fn len_bytes_field(&self) -> Option<&Ident> {
+        match &self.len_bytes {
+            Some(Expr::Path(path)) => path.path.get_ident(),
+            _ => None,
+        }
+    }
+
+    /// Returns the statement that serializes the field accessed by `val`.
+    fn serialize(&self, val: TokenStream, ty: &Type, lifetime: &Lifetime) -> TokenStream {
+        if let Some(serialize_with) = &self.serialize_with {
+            return quote! {
+                #serialize_with(&#val, __binary_buf)?;
+            };
+        }
+
+        match self.encoding() {
+            Some(encoding) => quote! {
+                <#encoding as ::binary::encoding::Encoding<#lifetime, #ty>>::serialize(&#val, __binary_buf)?;
+            },
+            None => quote! {
+                #val.serialize(__binary_buf)?;
+            },
+        }
+    }
+
+    /// Returns the expression that deserializes the field.
+    fn deserialize(&self, ty: &Type, lifetime: &Lifetime) -> TokenStream {
+        if let Some(deserialize_with) = &self.deserialize_with {
+            return quote! {
+                #deserialize_with(__binary_buf)?
+            };
+        }
+
+        match self.encoding() {
+            Some(encoding) => quote! {
+                <#encoding as ::binary::encoding::Encoding<#lifetime, #ty>>::deserialize(__binary_buf)?
+            },
+            None => quote! {
+                Binary::deserialize(__binary_buf)?
+            },
+        }
+    }
+
+    /// Returns the expression for the encoded length of the field accessed by `val`.
+    fn encoded_len(&self, val: TokenStream, ty: &Type, lifetime: &Lifetime) -> TokenStream {
+        if let Some(serialize_with) = &self.serialize_with {
+            return quote! {
+                ::binary::Counter::count(|__binary_buf| #serialize_with(&#val, __binary_buf))
+            };
+        }
+
+        match self.encoding() {
+            Some(encoding) => quote! {
+                <#encoding as ::binary::encoding::Encoding<#lifetime, #ty>>::encoded_len(&#val)
+            },
+            None => quote! {
+                #val.encoded_len()
+            },
+        }
+    }
+}
+
+/// Resolves the name of a datatype, such as `VarU32` or `U16BE`, the same way as the `datatype` of an
+/// enum. Any other name is parsed as a type that has to be in scope where the derive is used.
+fn datatype_type(name: &LitStr) -> Result<TokenStream> {
+    let datatype = match name.value().as_str() {
+        "I8" => quote!(::binary::datatypes::I8),
+        "U8" => quote!(::binary::datatypes::U8),
+        "I16" => quote!(::binary::datatypes::I16<::binary::encoding::LE>),
+        "U16" => quote!(::binary::datatypes::U16<::binary::encoding::LE>),
+        "I32" => quote!(::binary::datatypes::I32<::binary::encoding::LE>),
+        "U32" => quote!(::binary::datatypes::U32<::binary::encoding::LE>),
+        "I16BE" => quote!(::binary::datatypes::I16<::binary::encoding::BE>),
+        "U16BE" => quote!(::binary::datatypes::U16<::binary::encoding::BE>),
+        "I32BE" => quote!(::binary::datatypes::I32<::binary::encoding::BE>),
+        "U32BE" => quote!(::binary::datatypes::U32<::binary::encoding::BE>),
+        "I64" => quote!(::binary::datatypes::I64<::binary::encoding::LE>),
+        "U64" => quote!(::binary::datatypes::U64<::binary::encoding::LE>),
+        "I64BE" => quote!(::binary::datatypes::I64<::binary::encoding::BE>),
+        "U64BE" => quote!(::binary::datatypes::U64<::binary::encoding::BE>),
+        "VarI32" => quote!(::binary::datatypes::VarI32),
+        "VarU32" => quote!(::binary::datatypes::VarU32),
+        "VarI64" => quote!(::binary::datatypes::VarI64),
+        "VarU64" => quote!(::binary::datatypes::VarU64),
+        _ => name.parse::<Type>()?.to_token_stream(),
+    };
+
+    Ok(datatype)
 }
 
 /// Parses the tag attribute and returns it if exists.
-fn parse_tag_attr(attrs: &[Attribute]) -> Result<Option<usize>> {
+fn parse_tag_attr(attrs: &[Attribute]) -> Result<Option<i128>> {
     for attr in attrs {
         if attr.path().is_ident("variant") {
             let mut res = 0;
 
             attr.parse_nested_meta(|meta| {
                 if meta.path.is_ident("tag") {
-                    res = meta.value()?.parse::<LitInt>()?.base10_parse::<usize>()?;
+                    res = meta.value()?.parse::<LitInt>()?.base10_parse::<i128>()?;
                     Ok(())
                 } else {
                     Err(meta.error("unrecognized argument"))
@@ -388,10 +1000,31 @@ fn parse_tag_attr(attrs: &[Attribute]) -> Result<Option<usize>> {
     Ok(None)
 }
 
+/// Returns whether the variant has the `#[binary(other)]` attribute, which makes it store the
+/// discriminants that no other variant has.
+fn parse_other_attr(attrs: &[Attribute]) -> Result<bool> {
+    let mut res = false;
+
+    for attr in attrs {
+        if attr.path().is_ident("binary") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("other") {
+                    res = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized argument"))
+                }
+            })?;
+        }
+    }
+
+    Ok(res)
+}
+
 /// Adding our lifetime to the generics before calling `.split_for_impl()` would
 /// also add it to the resulting ty_generics, which we don't want. So I'm doing
 /// this hack.
-fn decode_split_for_impl(
+pub(crate) fn decode_split_for_impl(
     mut generics: Generics,
     lifetime: Lifetime,
 ) -> (TokenStream, TokenStream, TokenStream) {
@@ -412,7 +1045,71 @@ fn decode_split_for_impl(
     (impl_generics, ty_generics, where_clause)
 }
 
-fn add_trait_bounds(generics: &mut Generics, trait_: TokenStream) {
+/// Attributes of the type deriving `Binary`.
+#[derive(Default)]
+struct ContainerAttrs {
+    /// The where predicates of `#[binary(bound = "...")]`, which replace the bounds that are added to the
+    /// type parameters.
+    bound: Option<Punctuated<WherePredicate, Token![,]>>,
+    /// The type that encodes the discriminant of an enum, set by `#[binary(tag = ...)]` or by the older
+    /// `#[data(datatype = "...")]`.
+    tag: Option<TokenStream>,
+}
+
+impl ContainerAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut res = Self::default();
+
+        for attr in attrs {
+            if attr.path().is_ident("binary") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("bound") {
+                        let value = meta.value()?.parse::<LitStr>()?;
+                        res.bound = Some(value.parse_with(Punctuated::parse_terminated)?);
+                    } else if meta.path.is_ident("tag") {
+                        let value = meta.value()?;
+
+                        res.tag = Some(if value.peek(LitStr) {
+                            datatype_type(&value.parse()?)?
+                        } else {
+                            value.parse::<Type>()?.to_token_stream()
+                        });
+                    } else {
+                        return Err(meta.error("unrecognized argument"));
+                    }
+
+                    Ok(())
+                })?;
+            } else if attr.path().is_ident("data") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("datatype") {
+                        res.tag = Some(datatype_type(&meta.value()?.parse()?)?);
+                        Ok(())
+                    } else {
+                        Err(meta.error("unrecognized argument"))
+                    }
+                })?;
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Adds the bounds of the derived implementation: the ones of the `bound` attribute if there is one, or
+/// `Binary` for every type parameter otherwise.
+fn add_bounds(
+    generics: &mut Generics,
+    bound: Option<Punctuated<WherePredicate, Token![,]>>,
+    lifetime: &Lifetime,
+) {
+    match bound {
+        Some(bound) => generics.make_where_clause().predicates.extend(bound),
+        None => add_trait_bounds(generics, quote!(::binary::Binary<#lifetime>)),
+    }
+}
+
+pub(crate) fn add_trait_bounds(generics: &mut Generics, trait_: TokenStream) {
     for param in &mut generics.params {
         if let GenericParam::Type(type_param) = param {
             type_param.bounds.push(parse_quote!(#trait_))
@@ -1,16 +1,55 @@
 extern crate proc_macro;
 use binary::binary_derive;
+use nbt::nbt_derive;
+use packet::{packet_derive, registry as registry_impl};
 use proc_macro::TokenStream as StdTokenStream;
 
 mod binary;
+mod nbt;
+mod packet;
 
 ///
 /// Derives Binary trait for Structs and Enums
 ///
-#[proc_macro_derive(Binary, attributes(data, variant, skip))]
+#[proc_macro_derive(Binary, attributes(data, variant, skip, binary))]
 pub fn derive_binary(item: StdTokenStream) -> StdTokenStream {
     match binary_derive(item.into()) {
         Ok(val) => val.into(),
         Err(e) => e.into_compile_error().into(),
     }
 }
+
+///
+/// Derives ToTag, FromTag and Binary traits for Structs that are mapped to NBT compounds
+///
+#[proc_macro_derive(Nbt, attributes(nbt))]
+pub fn derive_nbt(item: StdTokenStream) -> StdTokenStream {
+    match nbt_derive(item.into()) {
+        Ok(val) => val.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+///
+/// Derives Packet trait for the game packets, with the id set by `#[packet(id = ...)]`
+///
+#[proc_macro_derive(Packet, attributes(packet))]
+pub fn derive_packet(item: StdTokenStream) -> StdTokenStream {
+    match packet_derive(item.into()) {
+        Ok(val) => val.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+///
+/// Generates the enum of the packets in a registry, which dispatches packets to their types by id.
+/// `Debug` is derived on the enum if its attributes do not derive it already.
+/// With `#[registry(deny_trailing_bytes)]`, data left after a registered packet is an error
+///
+#[proc_macro]
+pub fn registry(item: StdTokenStream) -> StdTokenStream {
+    match registry_impl(item.into()) {
+        Ok(val) => val.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
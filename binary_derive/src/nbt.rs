@@ -0,0 +1,245 @@
+use crate::binary::{add_trait_bounds, decode_split_for_impl};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{
+    parse2, parse_quote, Attribute, Data, DeriveInput, Error, Fields, LitStr, Path, Result, Token,
+    Type,
+};
+
+/// Derives the ToTag, FromTag and Binary traits on Structs, mapping them to and from NBT compounds.
+pub fn nbt_derive(item: TokenStream) -> Result<TokenStream> {
+    let mut input = parse2::<DeriveInput>(item)?;
+    let name = input.ident;
+
+    if input.generics.lifetimes().count() > 1 {
+        return Err(Error::new(
+            input.generics.params.span(),
+            "type deriving `Nbt` must have no more than one lifetime",
+        ));
+    }
+
+    let lifetime = input
+        .generics
+        .lifetimes()
+        .next()
+        .map(|l| l.lifetime.clone())
+        .unwrap_or_else(|| parse_quote!('a));
+
+    let encoding = parse_encoding_attr(&input.attrs)?;
+
+    let fields = match input.data {
+        Data::Struct(struct_) => match struct_.fields {
+            Fields::Named(fields) => fields.named,
+            fields => {
+                return Err(Error::new(
+                    fields.span(),
+                    "Cannot derive `Nbt` on structs without named fields",
+                ))
+            }
+        },
+        Data::Enum(enum_) => {
+            return Err(Error::new(
+                enum_.enum_token.span,
+                "Cannot derive `Nbt` on enums",
+            ))
+        }
+        Data::Union(union) => {
+            return Err(Error::new(
+                union.union_token.span,
+                "Cannot derive `Nbt` on unions",
+            ))
+        }
+    };
+
+    let mut to_tag = TokenStream::new();
+    let mut from_tag = TokenStream::new();
+
+    for f in &fields {
+        let ident = f.ident.as_ref().unwrap();
+        let attrs = FieldAttrs::parse(&f.attrs)?;
+
+        if attrs.skip {
+            from_tag.extend(quote! {
+                #ident: Default::default(),
+            });
+            continue;
+        }
+
+        let key = attrs
+            .rename
+            .unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+
+        let missing = match attrs.default {
+            Some(Some(path)) => quote!(#path()),
+            Some(None) => quote!(Default::default()),
+            None if is_option(&f.ty) => quote!(None),
+            None => quote! {
+                return Err(::binary::nbt::TagError::MissingField { field: #key })
+            },
+        };
+
+        if is_option(&f.ty) {
+            to_tag.extend(quote! {
+                if let Some(val) = &self.#ident {
+                    compound.insert(#key, ::binary::nbt::ToTag::to_tag(val));
+                }
+            });
+            from_tag.extend(quote! {
+                #ident: match compound.remove(#key) {
+                    Some(tag) => Some(::binary::nbt::FromTag::from_tag(tag).map_err(|e| e.in_field(#key))?),
+                    None => #missing,
+                },
+            });
+        } else {
+            to_tag.extend(quote! {
+                compound.insert(#key, ::binary::nbt::ToTag::to_tag(&self.#ident));
+            });
+            from_tag.extend(quote! {
+                #ident: match compound.remove(#key) {
+                    Some(tag) => ::binary::nbt::FromTag::from_tag(tag).map_err(|e| e.in_field(#key))?,
+                    None => #missing,
+                },
+            });
+        }
+    }
+
+    add_trait_bounds(
+        &mut input.generics,
+        quote!(::binary::nbt::ToTag + ::binary::nbt::FromTag<#lifetime> + ::std::fmt::Debug),
+    );
+
+    let (impl_generics, ty_generics, where_clause) =
+        decode_split_for_impl(input.generics, lifetime.clone());
+    let type_name = name.to_string();
+
+    Ok(quote! {
+        impl #impl_generics ::binary::nbt::ToTag for #name #ty_generics
+        #where_clause
+        {
+            fn to_tag(&self) -> ::binary::nbt::Tag<'_> {
+                let mut compound = ::binary::nbt::Compound::new();
+                #to_tag
+                ::binary::nbt::Tag::Compound(compound)
+            }
+        }
+
+        impl #impl_generics ::binary::nbt::FromTag<#lifetime> for #name #ty_generics
+        #where_clause
+        {
+            fn from_tag(tag: ::binary::nbt::Tag<#lifetime>) -> std::result::Result<Self, ::binary::nbt::TagError> {
+                let mut compound = <::binary::nbt::Compound<#lifetime> as ::binary::nbt::FromTag>::from_tag(tag)?;
+
+                Ok(Self {
+                    #from_tag
+                })
+            }
+        }
+
+        impl #impl_generics ::binary::Binary<#lifetime> for #name #ty_generics
+        #where_clause
+        {
+            fn serialize(&self, buf: &mut impl ::std::io::Write) -> std::result::Result<(), ::binary::error::EncodeError> {
+                use ::binary::Binary;
+
+                ::binary::nbt::Nbt::<#encoding>::new("", ::binary::nbt::ToTag::to_tag(self)).serialize(buf)
+            }
+
+            fn deserialize(buf: &mut impl ::binary::reader::Reader<#lifetime>) -> std::result::Result<Self, ::binary::error::DecodeError> {
+                use ::binary::Binary;
+                use ::binary::reader::Reader;
+
+                let offset = buf.position();
+                let nbt = ::binary::nbt::Nbt::<#encoding>::deserialize(buf)?;
+
+                ::binary::nbt::FromTag::from_tag(nbt.tag).map_err(|e| e.into_decode_error(#type_name, offset))
+            }
+        }
+    })
+}
+
+/// Attributes of a field that change how the field is mapped.
+#[derive(Default)]
+struct FieldAttrs {
+    /// Set with `#[nbt(skip)]`, the field is not written and is read as its default value.
+    skip: bool,
+    /// Set with `#[nbt(rename = "...")]`, the name of the tag if it is not the name of the field.
+    rename: Option<LitStr>,
+    /// Set with `#[nbt(default)]` or `#[nbt(default = "path")]`, the field is read as its default value,
+    /// or the value returned by the function, when the tag is missing.
+    default: Option<Option<Path>>,
+}
+
+impl FieldAttrs {
+    /// Parses the `nbt` attributes of a field.
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut res = Self::default();
+
+        for attr in attrs {
+            if attr.path().is_ident("nbt") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        res.skip = true;
+                    } else if meta.path.is_ident("rename") {
+                        res.rename = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("default") {
+                        let path = match meta.input.peek(Token![=]) {
+                            true => Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                            false => None,
+                        };
+
+                        res.default = Some(path);
+                    } else {
+                        return Err(meta.error("unrecognized argument"));
+                    }
+
+                    Ok(())
+                })?;
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Parses the `#[nbt(encoding = "...")]` attribute of the struct, which selects the flavour of NBT used by
+/// the `Binary` implementation. Defaults to the network flavour that is used in packets.
+fn parse_encoding_attr(attrs: &[Attribute]) -> Result<TokenStream> {
+    let mut encoding = quote!(::binary::nbt::NetworkLittleEndian);
+
+    for attr in attrs {
+        if attr.path().is_ident("nbt") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("encoding") {
+                    let value = meta.value()?.parse::<LitStr>()?;
+
+                    encoding = match value.value().as_str() {
+                        "LittleEndian" => quote!(::binary::nbt::LittleEndian),
+                        "NetworkLittleEndian" => quote!(::binary::nbt::NetworkLittleEndian),
+                        "BigEndian" => quote!(::binary::nbt::BigEndian),
+                        _ => value.parse::<Type>()?.to_token_stream(),
+                    };
+
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized argument"))
+                }
+            })?;
+        }
+    }
+
+    Ok(encoding)
+}
+
+/// Returns whether the type is an `Option`, which is written only when it is `Some` and read as `None`
+/// when the tag is missing.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
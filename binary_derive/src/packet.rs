@@ -0,0 +1,253 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{
+    parse2, parse_quote, Data, DeriveInput, Error, Fields, Lifetime, LitInt, Path, Result, Token,
+};
+
+/// The largest id that fits into the header of a packet.
+const MAX_ID: u32 = 0x3FF;
+
+/// Derives the Packet trait, which holds the id set by the `#[packet(id = ...)]` attribute.
+pub fn packet_derive(item: TokenStream) -> Result<TokenStream> {
+    let input = parse2::<DeriveInput>(item)?;
+    let name = input.ident;
+
+    let mut id = None;
+
+    for attr in &input.attrs {
+        if attr.path().is_ident("packet") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("id") {
+                    let lit = meta.value()?.parse::<LitInt>()?;
+                    let val = lit.base10_parse::<u32>()?;
+
+                    if val > MAX_ID {
+                        return Err(Error::new(
+                            lit.span(),
+                            format!("packet id must be at most {:#x}", MAX_ID),
+                        ));
+                    }
+
+                    id = Some(val);
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized argument"))
+                }
+            })?;
+        }
+    }
+
+    let id = id.ok_or_else(|| {
+        Error::new(
+            name.span(),
+            "deriving `Packet` requires the `#[packet(id = ...)]` attribute",
+        )
+    })?;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::binary::packet::Packet for #name #ty_generics #where_clause {
+            const ID: u32 = #id;
+        }
+    })
+}
+
+/// Generates the enum of the packets registered in the `registry!` macro, adding the `Unknown` variant
+/// for the ids that are not registered, and implements `Registry` and `From` for every packet on it.
+///
+/// Registering two packets with the same id is a compile-time error. The data left after a registered
+/// packet is ignored, unless the enum has the `#[registry(deny_trailing_bytes)]` attribute.
+pub fn registry(item: TokenStream) -> Result<TokenStream> {
+    let input = parse2::<DeriveInput>(item)?;
+    let name = &input.ident;
+
+    let mut deny_trailing_bytes = false;
+    let mut derives_debug = false;
+    let mut attrs = Vec::new();
+
+    for attr in &input.attrs {
+        if attr.path().is_ident("derive") {
+            let paths = attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)?;
+            derives_debug |= paths
+                .iter()
+                .any(|path| path.segments.last().is_some_and(|s| s.ident == "Debug"));
+        }
+
+        if attr.path().is_ident("registry") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("deny_trailing_bytes") {
+                    deny_trailing_bytes = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized argument"))
+                }
+            })?;
+        } else {
+            attrs.push(attr);
+        }
+    }
+
+    let mut lifetimes = input.generics.lifetimes();
+    let lifetime = match (lifetimes.next(), lifetimes.next()) {
+        (Some(lifetime), None) if input.generics.type_params().count() == 0 => {
+            lifetime.lifetime.clone()
+        }
+        _ => {
+            return Err(Error::new(
+                input.generics.span(),
+                "packet registry must have exactly one lifetime, which unknown packets borrow their data with",
+            ))
+        }
+    };
+
+    let Data::Enum(enum_) = &input.data else {
+        return Err(Error::new(name.span(), "packet registry must be an enum"));
+    };
+
+    let mut variants = Vec::new();
+
+    for variant in &enum_.variants {
+        match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                variants.push((&variant.ident, &fields.unnamed[0].ty));
+            }
+            _ => {
+                return Err(Error::new(
+                    variant.span(),
+                    "variants of a packet registry must hold exactly one packet",
+                ))
+            }
+        }
+    }
+
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let idents = variants.iter().map(|(ident, _)| ident).collect::<Vec<_>>();
+    let types = variants.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+
+    // The ids are matched on and compared in a constant, where the packets can only be named with the
+    // `'static` lifetime.
+    let static_types = types
+        .iter()
+        .map(|ty| {
+            let mut ty = (**ty).clone();
+            StaticLifetimes.visit_type_mut(&mut ty);
+            ty
+        })
+        .collect::<Vec<_>>();
+    let messages = idents.iter().map(|ident| {
+        format!(
+            "`{}` has the same id as another packet of `{}`",
+            ident, name
+        )
+    });
+
+    let check = deny_trailing_bytes.then(|| {
+        quote! {
+            let remaining = ::binary::reader::Reader::remaining(buf);
+
+            if remaining > 0 {
+                return Err(::binary::error::DecodeError::TrailingBytes {
+                    offset: ::binary::reader::Reader::position(buf),
+                    remaining,
+                });
+            }
+        }
+    });
+
+    // `Debug` is derived unless the attributes of the enum already derive it.
+    let derive_debug = (!derives_debug).then(|| quote!(#[derive(Debug)]));
+
+    Ok(quote! {
+        #(#attrs)*
+        #derive_debug
+        #vis enum #name #generics {
+            #(#idents(#types),)*
+            Unknown(::binary::packet::UnknownPacket<#lifetime>),
+        }
+
+        impl #generics ::binary::packet::Registry<#lifetime> for #name<#lifetime> {
+            fn id(&self) -> u32 {
+                match self {
+                    #(Self::#idents(_) => <#types as ::binary::packet::Packet>::ID,)*
+                    Self::Unknown(packet) => packet.id,
+                }
+            }
+
+            fn decode(
+                id: u32,
+                buf: &mut impl ::binary::reader::Reader<#lifetime>,
+            ) -> std::result::Result<Self, ::binary::error::DecodeError> {
+                let packet = match id {
+                    #(
+                        <#static_types as ::binary::packet::Packet>::ID => {
+                            Self::#idents(::binary::Binary::deserialize(buf)?)
+                        }
+                    )*
+                    _ => Self::Unknown(::binary::packet::UnknownPacket {
+                        id,
+                        data: ::binary::Binary::deserialize(buf)?,
+                    }),
+                };
+
+                #check
+                Ok(packet)
+            }
+
+            fn encode(
+                &self,
+                buf: &mut impl std::io::Write,
+            ) -> std::result::Result<(), ::binary::error::EncodeError> {
+                match self {
+                    #(Self::#idents(packet) => ::binary::Binary::serialize(packet, buf),)*
+                    Self::Unknown(packet) => ::binary::Binary::serialize(&packet.data, buf),
+                }
+            }
+
+            fn encoded_len(&self) -> usize {
+                match self {
+                    #(Self::#idents(packet) => ::binary::Binary::encoded_len(packet),)*
+                    Self::Unknown(packet) => ::binary::Binary::encoded_len(&packet.data),
+                }
+            }
+        }
+
+        #(
+            impl #generics From<#types> for #name<#lifetime> {
+                fn from(packet: #types) -> Self {
+                    Self::#idents(packet)
+                }
+            }
+        )*
+
+        const _: () = {
+            let ids = [#(<#static_types as ::binary::packet::Packet>::ID),*];
+            let messages = [#(#messages),*];
+
+            let mut i = 0;
+            while i < ids.len() {
+                let mut j = 0;
+                while j < i {
+                    if ids[i] == ids[j] {
+                        panic!("{}", messages[i]);
+                    }
+                    j += 1;
+                }
+                i += 1;
+            }
+        };
+    })
+}
+
+/// Replaces every lifetime of a type with `'static`.
+struct StaticLifetimes;
+
+impl VisitMut for StaticLifetimes {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        *lifetime = parse_quote!('static);
+    }
+}
@@ -0,0 +1,442 @@
+//!
+//! These tests feed truncated and garbage buffers to every `Binary` implementation and make sure that
+//! decoding returns an error instead of panicking.
+//!
+
+use binary::composite::{BlockPos, RotationByte, Uuid, Vec2, Vec3};
+use binary::datatypes::{
+    Bool, Strict, VarI32, VarI64, VarU32, VarU64, F32, F64, I16, I24, I32, I64, I8, U16, U24, U32,
+    U64, U8,
+};
+use binary::nbt::{BigEndian, Compound, LittleEndian, Nbt, NetworkLittleEndian, Tag};
+use binary::packet::{Framed, Header};
+use binary::prefixed::{Array, Bytes, Str, UnsizedArray, UnsizedBytes};
+use binary::reader::SliceReader;
+use binary::Binary;
+use binary_derive::{registry, Binary, Packet};
+use byteorder::{BE, LE};
+use bytes::BytesMut;
+use std::io::Write;
+
+#[derive(Debug, Binary, Packet)]
+#[packet(id = 0x01)]
+struct Packet<'a> {
+    id: VarU32,
+    name: Str<'a, VarU32>,
+    flags: Option<U16<LE>>,
+    values: Array<'a, VarI64, U16<BE>>,
+}
+
+#[derive(Debug, Binary)]
+#[data(datatype = "VarU32")]
+enum Action<'a> {
+    Jump,
+    Say(Str<'a, U16<LE>>),
+    Move { x: F32<LE>, y: F32<LE> },
+}
+
+#[derive(Debug, Binary)]
+#[binary(tag = VarI64)]
+enum Effect {
+    #[variant(tag = -1)]
+    Clear,
+    Add(VarI32, U8),
+    #[variant(tag = 1099511627776)]
+    Custom {
+        id: VarU32,
+    },
+}
+
+#[derive(Debug, Binary)]
+struct Counted<'a> {
+    count: U8,
+    #[binary(be)]
+    bit_len: u16,
+    #[binary(count = "count")]
+    names: Vec<Str<'a, VarU32>>,
+    #[binary(len_bytes = "(bit_len as usize).div_ceil(8)")]
+    bits: UnsizedBytes<'a>,
+}
+
+#[derive(Debug, Binary, Packet)]
+#[packet(id = 0x02)]
+struct Chat<'a> {
+    sender: Str<'a, VarU32>,
+    message: Str<'a, VarU32>,
+}
+
+registry! {
+    enum GamePacket<'a> {
+        Packet(Packet<'a>),
+        Chat(Chat<'a>),
+    }
+}
+
+/// Small xorshift generator so that the garbage buffers are the same on every run.
+struct Garbage(u64);
+
+impl Garbage {
+    fn next(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as u8
+    }
+
+    fn buffer(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next()).collect()
+    }
+}
+
+/// Serializes the value and checks that decoding every strict prefix of the encoding fails.
+fn assert_truncated_fails<'a, B: Binary<'a>>(val: &B, storage: &'a mut BytesMut) {
+    val.serialize(storage).unwrap();
+    let bytes: &'a [u8] = storage;
+
+    for len in 0..bytes.len() {
+        let mut reader = SliceReader::new(&bytes[..len]);
+        assert!(
+            B::deserialize(&mut reader).is_err(),
+            "{:?} decoded from {} of {} bytes",
+            val,
+            len,
+            bytes.len()
+        );
+    }
+}
+
+/// Decodes the value from garbage buffers of various lengths, only checking that nothing panics.
+fn decode_garbage<B: for<'a> Binary<'a>>() {
+    let mut garbage = Garbage(0x2545F4914F6CDD1D);
+
+    for len in 0..64 {
+        for _ in 0..64 {
+            let data = garbage.buffer(len);
+            let _ = B::deserialize(&mut SliceReader::new(&data[..]));
+        }
+    }
+}
+
+/// Same as `decode_garbage` but for lifetime based types that borrow from the buffer.
+fn decode_garbage_borrowed(decode: impl Fn(&mut SliceReader)) {
+    let mut garbage = Garbage(0x9E3779B97F4A7C15);
+
+    for len in 0..64 {
+        for _ in 0..64 {
+            let data = garbage.buffer(len);
+            decode(&mut SliceReader::new(&data[..]));
+        }
+    }
+}
+
+#[test]
+fn test_truncated_datatypes() {
+    assert_truncated_fails(&Bool::new(true), &mut BytesMut::new());
+    assert_truncated_fails(&U8::new(1), &mut BytesMut::new());
+    assert_truncated_fails(&I8::new(-1), &mut BytesMut::new());
+    assert_truncated_fails(&U16::<LE>::new(1), &mut BytesMut::new());
+    assert_truncated_fails(&I16::<BE>::new(-1), &mut BytesMut::new());
+    assert_truncated_fails(&U24::<LE>::new(1), &mut BytesMut::new());
+    assert_truncated_fails(&I24::<BE>::new(-1), &mut BytesMut::new());
+    assert_truncated_fails(&U32::<LE>::new(1), &mut BytesMut::new());
+    assert_truncated_fails(&I32::<BE>::new(-1), &mut BytesMut::new());
+    assert_truncated_fails(&U64::<LE>::new(1), &mut BytesMut::new());
+    assert_truncated_fails(&I64::<BE>::new(-1), &mut BytesMut::new());
+    assert_truncated_fails(&F32::<LE>::new(1.5), &mut BytesMut::new());
+    assert_truncated_fails(&F64::<BE>::new(-1.5), &mut BytesMut::new());
+    assert_truncated_fails(&VarI32::new(i32::MIN), &mut BytesMut::new());
+    assert_truncated_fails(&VarU32::new(u32::MAX), &mut BytesMut::new());
+    assert_truncated_fails(&VarI64::new(i64::MIN), &mut BytesMut::new());
+    assert_truncated_fails(&VarU64::new(u64::MAX), &mut BytesMut::new());
+    assert_truncated_fails(&Some(U32::<LE>::new(1)), &mut BytesMut::new());
+    assert_truncated_fails(&[U32::<LE>::new(1), U32::new(2)], &mut BytesMut::new());
+    assert_truncated_fails(&(U8::new(1), VarU64::new(u64::MAX)), &mut BytesMut::new());
+    assert_truncated_fails(&Box::new(I64::<LE>::new(-1)), &mut BytesMut::new());
+    assert_truncated_fails(&Vec2::new(1.0, 2.0), &mut BytesMut::new());
+    assert_truncated_fails(&Vec3::new(1.0, 2.0, 3.0), &mut BytesMut::new());
+    assert_truncated_fails(&BlockPos::new(-300, -64, i32::MAX), &mut BytesMut::new());
+    assert_truncated_fails(&Uuid::new(u128::MAX), &mut BytesMut::new());
+    assert_truncated_fails(&RotationByte::new(7), &mut BytesMut::new());
+}
+
+#[test]
+fn test_truncated_nbt() {
+    let mut compound = Compound::new();
+    compound.insert("name", "Steve");
+    compound.insert("health", 20.0f32);
+    compound.insert("pos", vec![Tag::Int(-300), Tag::Int(64), Tag::Int(300)]);
+    compound.insert("seeds", vec![i64::MIN, i64::MAX]);
+
+    let mut nested = Compound::new();
+    nested.insert("bytes", Tag::ByteArray(vec![1, 2, 3].into()));
+    compound.insert("nested", nested);
+
+    assert_truncated_fails(
+        &Nbt::<LittleEndian>::compound(compound.clone()),
+        &mut BytesMut::new(),
+    );
+    assert_truncated_fails(
+        &Nbt::<NetworkLittleEndian>::compound(compound.clone()),
+        &mut BytesMut::new(),
+    );
+    assert_truncated_fails(&Nbt::<BigEndian>::compound(compound), &mut BytesMut::new());
+}
+
+#[test]
+fn test_invalid_nbt_ids() {
+    use binary::error::DecodeError;
+
+    // The error points at the id, not at the name or the length after it.
+    let data = [10u8, 0, 0, 13, 1, 0, b'a', 0];
+    let err = Nbt::<LittleEndian>::deserialize(&mut SliceReader::new(&data[..])).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 3,
+            value: 13
+        }
+    );
+
+    let data = [9u8, 0, 0, 20, 1, 0, 0, 0];
+    let err = Nbt::<LittleEndian>::deserialize(&mut SliceReader::new(&data[..])).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 3,
+            value: 20
+        }
+    );
+
+    // Empty lists must have a valid element id as well.
+    let data = [9u8, 0, 0, 20, 0, 0, 0, 0];
+    let err = Nbt::<LittleEndian>::deserialize(&mut SliceReader::new(&data[..])).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 3,
+            value: 20
+        }
+    );
+
+    let data = [13u8, 0, 0];
+    let err = Nbt::<LittleEndian>::deserialize(&mut SliceReader::new(&data[..])).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 0,
+            value: 13
+        }
+    );
+}
+
+#[test]
+fn test_nbt_array_limits() {
+    use binary::error::DecodeError;
+    use binary::reader::Limits;
+
+    let limits = Limits {
+        max_elements: 2,
+        ..Limits::default()
+    };
+
+    // Byte arrays are limited the same way as the other arrays.
+    for id in [7u8, 11, 12] {
+        let data = [id, 0, 0, 3, 0, 0, 0];
+        let err =
+            Nbt::<LittleEndian>::deserialize(&mut SliceReader::with_limits(&data[..], limits))
+                .unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::LengthLimit {
+                offset: 3,
+                len: 3,
+                limit: 2
+            }
+        );
+    }
+}
+
+#[test]
+fn test_invalid_header() {
+    use binary::error::DecodeError;
+
+    // Bits above the sub-client ids are rejected instead of being masked into another packet id.
+    for data in [&[0x80u8, 0x80, 0x01][..], &[0x81, 0xFF, 0xFF, 0xFF, 0x0F]] {
+        let err = Header::deserialize(&mut SliceReader::new(data)).unwrap_err();
+        assert!(matches!(err, DecodeError::Custom { offset: 0, .. }));
+    }
+
+    let header = Header::deserialize(&mut SliceReader::new(&[0xFF, 0x7F])).unwrap();
+    assert_eq!(
+        header,
+        Header {
+            id: 0x3FF,
+            sender: 3,
+            target: 3
+        }
+    );
+}
+
+#[test]
+fn test_nested_nbt() {
+    // A root list of lists of lists... must stop at the depth limit instead of overflowing the stack.
+    let mut data = vec![9u8, 0, 0];
+    for _ in 0..100_000 {
+        data.extend_from_slice(&[9, 1, 0, 0, 0]);
+    }
+
+    let err = Nbt::<LittleEndian>::deserialize(&mut SliceReader::new(&data[..])).unwrap_err();
+    assert!(matches!(err, binary::error::DecodeError::DepthLimit { .. }));
+}
+
+#[test]
+fn test_truncated_prefixed() {
+    assert_truncated_fails(&Str::<VarU32>::new("Hello world"), &mut BytesMut::new());
+    assert_truncated_fails(&Str::<U16<LE>>::new("ẞ"), &mut BytesMut::new());
+    assert_truncated_fails(
+        &Array::<VarI32, I32<BE>>::new(vec![VarI32::new(-300), VarI32::new(300)]),
+        &mut BytesMut::new(),
+    );
+    assert_truncated_fails(&Bytes::<VarU32>::new(&[1, 2, 3]), &mut BytesMut::new());
+    assert_truncated_fails(&Bytes::<I32<BE>>::new(&[]), &mut BytesMut::new());
+
+    // Reading the rest of the buffer never fails, not even when the position is past the end.
+    let data = [1u8, 2, 3];
+    let mut reader = SliceReader::new(&data[..]);
+    reader.set_position(10);
+    assert_eq!(UnsizedBytes::deserialize(&mut reader).unwrap().len(), 0);
+}
+
+#[test]
+fn test_truncated_derived() {
+    let packet = Packet {
+        id: VarU32::new(0x09),
+        name: Str::new("Steve"),
+        flags: Some(U16::new(3)),
+        values: Array::new(vec![VarI64::new(1), VarI64::new(-1)]),
+    };
+    assert_truncated_fails(&packet, &mut BytesMut::new());
+
+    assert_truncated_fails(&Action::Say(Str::new("hi")), &mut BytesMut::new());
+    assert_truncated_fails(
+        &Effect::Add(VarI32::new(-5), U8::new(1)),
+        &mut BytesMut::new(),
+    );
+    assert_truncated_fails(
+        &Effect::Custom {
+            id: VarU32::new(300),
+        },
+        &mut BytesMut::new(),
+    );
+    assert_truncated_fails(
+        &Counted {
+            count: U8::new(0),
+            bit_len: 12,
+            names: vec![Str::new("a"), Str::new("b")],
+            bits: UnsizedBytes::new(&[0xAB, 0x0C]),
+        },
+        &mut BytesMut::new(),
+    );
+    assert_truncated_fails(
+        &Action::Move {
+            x: F32::new(1.0),
+            y: F32::new(2.0),
+        },
+        &mut BytesMut::new(),
+    );
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let data = [2u8, 0xC3, 0x28];
+    let err = Str::<VarU32>::deserialize(&mut SliceReader::new(&data[..])).unwrap_err();
+
+    assert!(matches!(
+        err,
+        binary::error::DecodeError::InvalidUtf8 { offset: 1, .. }
+    ));
+}
+
+#[test]
+fn test_garbage() {
+    decode_garbage::<Bool>();
+    decode_garbage::<U8>();
+    decode_garbage::<I8>();
+    decode_garbage::<U16<LE>>();
+    decode_garbage::<I16<LE>>();
+    decode_garbage::<U24<BE>>();
+    decode_garbage::<I24<BE>>();
+    decode_garbage::<U32<LE>>();
+    decode_garbage::<I32<LE>>();
+    decode_garbage::<U64<BE>>();
+    decode_garbage::<I64<BE>>();
+    decode_garbage::<F32<LE>>();
+    decode_garbage::<F64<LE>>();
+    decode_garbage::<VarI32>();
+    decode_garbage::<VarU32>();
+    decode_garbage::<VarI64>();
+    decode_garbage::<VarU64>();
+    decode_garbage::<Strict<VarI32>>();
+    decode_garbage::<Strict<VarU64>>();
+    decode_garbage::<Option<VarU32>>();
+    decode_garbage::<[VarI32; 4]>();
+    decode_garbage::<(U8, VarU64, Option<F64<BE>>)>();
+    decode_garbage::<Box<Option<Box<VarI64>>>>();
+    decode_garbage::<Vec3>();
+    decode_garbage::<BlockPos>();
+    decode_garbage::<Uuid>();
+
+    decode_garbage_borrowed(|buf| {
+        let _ = Str::<VarU32>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Str::<I16<LE>>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Array::<Str<U16<BE>>, VarU32>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Array::<U8, I32<LE>>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = UnsizedBytes::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Bytes::<VarI32>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = UnsizedArray::<Str<VarU32>>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Packet::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Action::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Effect::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        // Every header that is decoded can be written back.
+        if let Ok(header) = Header::deserialize(buf) {
+            assert!(header.to_bytes().is_ok());
+        }
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Framed::<GamePacket>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        if let Ok(counted) = Counted::deserialize(buf) {
+            assert_eq!(counted.count.0 as usize, counted.names.len());
+        }
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Nbt::<LittleEndian>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Nbt::<NetworkLittleEndian>::deserialize(buf);
+    });
+    decode_garbage_borrowed(|buf| {
+        let _ = Nbt::<BigEndian>::deserialize(buf);
+    });
+}
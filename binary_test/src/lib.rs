@@ -1,14 +1,18 @@
+#[cfg(test)]
+mod hostile;
+
 ///
 /// This test tests the encoding and decoding of conditional fields with the `[skip]` attribute.
 ///
 #[test]
 fn test_conditional() {
     use binary::datatypes::U16;
+    use binary::reader::SliceReader;
     use binary::Binary;
     use binary_derive::Binary;
     use byteorder::LE;
     use bytes::BytesMut;
-    use std::io::{Cursor, Write};
+    use std::io::Write;
 
     #[derive(Debug, Binary)]
     struct Test {
@@ -22,9 +26,9 @@ fn test_conditional() {
         short: U16::new(100),
     };
 
-    test.serialize(&mut bytes);
+    test.serialize(&mut bytes).unwrap();
 
-    let mut reader = Cursor::new(&bytes[..]);
+    let mut reader = SliceReader::new(&bytes[..]);
     let test2 = Test::deserialize(&mut reader).unwrap();
 
     assert_eq!(test2.short.0, 0);
@@ -38,12 +42,13 @@ fn test_conditional() {
 fn test_serde() {
     use binary::datatypes::{U16, U24, U8};
     use binary::prefixed::Str;
+    use binary::reader::SliceReader;
     use binary::Binary;
     use binary_derive::Binary;
     use byteorder::{BE, LE};
     use bytes::BytesMut;
     use std::env;
-    use std::io::{Cursor, Write};
+    use std::io::Write;
 
     env::set_var("RUST_BACKTRACE", "1");
 
@@ -64,11 +69,11 @@ fn test_serde() {
         u24: U24::new(102),
     };
 
-    ser.serialize(&mut bytes);
+    ser.serialize(&mut bytes).unwrap();
 
     println!("{:?}", bytes.to_vec());
 
-    let mut reader = Cursor::new(&bytes[..]);
+    let mut reader = SliceReader::new(&bytes[..]);
     let de = Test::deserialize(&mut reader).unwrap();
 
     assert_eq!(ser.short, de.short);
@@ -76,3 +81,2335 @@ fn test_serde() {
     assert_eq!(ser.str, de.str);
     assert_eq!(ser.u24, de.u24);
 }
+
+///
+/// This test tests that decoding errors report what went wrong and the byte offset at which it happened.
+///
+#[test]
+fn test_decode_error() {
+    use binary::datatypes::{VarU32, U16, U8};
+    use binary::error::DecodeError;
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::LE;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Test {
+        byte: U8,
+        short: U16<LE>,
+    }
+
+    #[derive(Debug, Binary)]
+    #[data(datatype = "U8")]
+    enum Kind {
+        First,
+        Second,
+    }
+
+    let mut reader = SliceReader::new(&[1u8, 2][..]);
+    let err = Test::deserialize(&mut reader).unwrap_err();
+    assert_eq!(err.root(), &DecodeError::UnexpectedEof { offset: 1 });
+    assert_eq!(err.offset(), 1);
+
+    let mut reader = SliceReader::new(&[0x80u8, 0x80, 0x80, 0x80, 0x80, 0x01][..]);
+    let err = VarU32::deserialize(&mut reader).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::VarIntOverflow {
+            offset: 0,
+            max_bytes: 5
+        }
+    );
+
+    let mut reader = SliceReader::new(&[1u8, 7][..]);
+    assert!(matches!(Kind::deserialize(&mut reader), Ok(Kind::Second)));
+    let err = Kind::deserialize(&mut reader).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 1,
+            value: 7
+        }
+    );
+    assert_eq!(err.offset(), 1);
+}
+
+///
+/// This test tests that serialization returns an error instead of panicking when the writer is full or
+/// when a length does not fit into its prefix.
+///
+#[test]
+fn test_encode_error() {
+    use binary::datatypes::{U16, U32, U8};
+    use binary::error::EncodeError;
+    use binary::prefixed::{Array, Str};
+    use binary::Binary;
+    use byteorder::LE;
+    use bytes::BytesMut;
+    use std::io::Cursor;
+
+    let mut slice = [0u8; 2];
+    let mut writer = Cursor::new(&mut slice[..]);
+    let err = U32::<LE>::new(10).serialize(&mut writer).unwrap_err();
+    assert!(matches!(err, EncodeError::Io(_)));
+
+    let array = Array::<U8, U16<LE>>::new(vec![U8::new(0); 65536]);
+    let err = array.serialize(&mut BytesMut::new()).unwrap_err();
+    assert!(matches!(
+        err,
+        EncodeError::LengthOverflow {
+            len: 65536,
+            max: 65535
+        }
+    ));
+
+    let long = "a".repeat(70000);
+    let err = Str::<U16<LE>>::new(&long)
+        .serialize(&mut BytesMut::new())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        EncodeError::LengthOverflow {
+            len: 70000,
+            max: 65535
+        }
+    ));
+}
+
+///
+/// This test tests that the limits of the reader reject lengths, depths and sizes that are too big.
+///
+#[test]
+fn test_limits() {
+    use binary::datatypes::{VarI32, VarU32, U16, U8};
+    use binary::encoding::{Encoding, Prefixed, LE};
+    use binary::error::{DecodeError, PathSegment};
+    use binary::prefixed::{Array, Str};
+    use binary::reader::{Limits, SliceReader};
+    use binary::Binary;
+
+    // A negative length behind a signed prefix is rejected instead of wrapping around.
+    let data = [0x01u8, 0x00, 0x00];
+    let err = Array::<U8, VarI32>::deserialize(&mut SliceReader::new(&data)).unwrap_err();
+    assert_eq!(err, DecodeError::NegativeLength { offset: 0, len: -1 });
+
+    let limits = Limits {
+        max_elements: 2,
+        max_string_len: 4,
+        max_bytes: 8,
+        max_depth: 1,
+    };
+
+    let data = [3u8, 0, 0, 0];
+    let err = Array::<U8, VarU32>::deserialize(&mut SliceReader::with_limits(&data, limits));
+    assert_eq!(
+        err.unwrap_err(),
+        DecodeError::LengthLimit {
+            offset: 0,
+            len: 3,
+            limit: 2
+        }
+    );
+
+    let data = [5u8, 0, b'h', b'e', b'l', b'l', b'o'];
+    let err = Str::<U16<LE>>::deserialize(&mut SliceReader::with_limits(&data, limits));
+    assert_eq!(
+        err.unwrap_err(),
+        DecodeError::LengthLimit {
+            offset: 0,
+            len: 5,
+            limit: 4
+        }
+    );
+
+    let data = [2u8, 2, 0, 0, 2, 0, 0];
+    let err = Array::<Array<U8, VarU32>, VarU32>::deserialize(&mut SliceReader::with_limits(
+        &data, limits,
+    ));
+    assert_eq!(
+        err.unwrap_err().root(),
+        &DecodeError::DepthLimit {
+            offset: 2,
+            limit: 1
+        }
+    );
+
+    // Vectors nested with encodings are limited the same way.
+    type Nested = Prefixed<VarU32, Prefixed<VarU32, LE>>;
+    let err = <Nested as Encoding<Vec<Vec<i16>>>>::deserialize(&mut SliceReader::with_limits(
+        &data, limits,
+    ));
+    assert_eq!(
+        err.unwrap_err(),
+        DecodeError::InField {
+            ty: None,
+            path: vec![PathSegment::Index(0)],
+            error: Box::new(DecodeError::DepthLimit {
+                offset: 2,
+                limit: 1
+            }),
+        }
+    );
+
+    let data = [0u8; 16];
+    let mut reader = SliceReader::with_limits(&data, limits);
+    let err = (0..16)
+        .map(|_| U8::deserialize(&mut reader))
+        .find_map(Result::err);
+    assert_eq!(
+        err,
+        Some(DecodeError::LengthLimit {
+            offset: 8,
+            len: 9,
+            limit: 8
+        })
+    );
+
+    let mut reader = SliceReader::with_limits(&data, Limits::UNLIMITED);
+    assert!((0..16).all(|_| U8::deserialize(&mut reader).is_ok()));
+}
+
+///
+/// This test tests that owned types can be decoded into a struct without a lifetime that can be sent to
+/// another thread, and converted to and from the borrowed types.
+///
+#[test]
+fn test_owned() {
+    use binary::datatypes::{VarU32, U16};
+    use binary::prefixed::{Array, OwnedArray, OwnedStr, OwnedUnsizedBytes, Str, UnsizedBytes};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::LE;
+    use bytes::BytesMut;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Text {
+        message: OwnedStr<VarU32>,
+        targets: OwnedArray<OwnedStr<U16<LE>>, VarU32>,
+        extra: OwnedUnsizedBytes,
+    }
+
+    fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+    let text = Text {
+        message: OwnedStr::new("Hello world"),
+        targets: OwnedArray::new(vec![OwnedStr::new("Steve"), OwnedStr::new("Alex")]),
+        extra: OwnedUnsizedBytes::new(vec![1, 2, 3]),
+    };
+
+    let mut bytes = BytesMut::new();
+    text.serialize(&mut bytes).unwrap();
+
+    let decoded = {
+        let data = bytes.to_vec();
+        Text::deserialize(&mut SliceReader::new(&data)).unwrap()
+    };
+    assert_send_static(&decoded);
+
+    let decoded = std::thread::spawn(move || decoded).join().unwrap();
+    assert_eq!(decoded.message, text.message);
+    assert_eq!(decoded.targets, text.targets);
+    assert_eq!(decoded.extra, text.extra);
+
+    let borrowed: Str<VarU32> = (&decoded.message).into();
+    assert_eq!(*borrowed, "Hello world");
+    assert_eq!(OwnedStr::from(borrowed), decoded.message);
+
+    let array: Array<VarU32, VarU32> = OwnedArray::new(vec![VarU32::new(1)]).into();
+    assert_eq!(OwnedArray::from(array).len(), 1);
+
+    let raw: UnsizedBytes = (&decoded.extra).into();
+    assert_eq!(OwnedUnsizedBytes::from(raw), decoded.extra);
+}
+
+///
+/// This test tests that the encoded length of values matches the amount of bytes they serialize into.
+///
+#[test]
+fn test_encoded_len() {
+    use binary::datatypes::{VarI32, VarI64, VarU32, VarU64, F32, U16, U24, U8};
+    use binary::prefixed::{Array, OwnedStr, Str, UnsizedBytes};
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::{BE, LE};
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Test<'a> {
+        byte: U8,
+        name: Str<'a, VarU32>,
+        position: Option<F32<LE>>,
+        ids: Array<'a, VarI64, U16<BE>>,
+        #[skip]
+        cache: U24<LE>,
+        rest: UnsizedBytes<'a>,
+    }
+
+    #[derive(Debug, Binary)]
+    #[data(datatype = "VarI32")]
+    enum Kind<'a> {
+        None,
+        #[variant(tag = 300)]
+        Named(OwnedStr<VarU32>, Str<'a, U16<LE>>),
+        Moved {
+            x: VarU64,
+        },
+    }
+
+    fn check<'a>(val: &impl Binary<'a>) {
+        let bytes = val.to_bytes().unwrap();
+        assert_eq!(val.encoded_len(), bytes.len(), "{:?}", val);
+    }
+
+    for n in [0, 1, 63, 64, -64, -65, 8191, i32::MAX, i32::MIN] {
+        check(&VarI32::new(n));
+        check(&VarU32::new(n as u32));
+        check(&VarI64::new(n as i64 * 1_000_000_000));
+        check(&VarU64::new(n as u64));
+    }
+
+    let test = Test {
+        byte: U8::new(1),
+        name: Str::new("Steve"),
+        position: Some(F32::new(1.0)),
+        ids: Array::new(vec![VarI64::new(1), VarI64::new(i64::MIN)]),
+        cache: U24::new(10),
+        rest: UnsizedBytes::new(&[1, 2, 3]),
+    };
+    check(&test);
+    // The skipped field does not take any bytes.
+    assert_eq!(test.encoded_len(), 1 + 6 + 5 + 13 + 3);
+    assert_eq!(test.cache.0, 10);
+    check(&Kind::None);
+    check(&Kind::Named(OwnedStr::new("a"), Str::new("bc")));
+    check(&Kind::Moved {
+        x: VarU64::new(u64::MAX),
+    });
+}
+
+///
+/// This test tests decoding from non-contiguous buffers with the `BufReader`, and that decoding from a
+/// slice still borrows from it.
+///
+#[test]
+fn test_buf_reader() {
+    use binary::datatypes::{VarU32, U16, U32};
+    use binary::prefixed::{Array, OwnedUnsizedBytes, Str};
+    use binary::reader::{BufReader, Reader, SliceReader};
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::LE;
+    use bytes::{Buf, Bytes};
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Test<'a> {
+        id: U32<LE>,
+        name: Str<'a, VarU32>,
+        values: Array<'a, U16<LE>, VarU32>,
+        rest: OwnedUnsizedBytes,
+    }
+
+    let test = Test {
+        id: U32::new(0xDEADBEEF),
+        name: Str::new("Hello world"),
+        values: Array::new(vec![U16::new(1), U16::new(2)]),
+        rest: OwnedUnsizedBytes::new(vec![9, 8, 7]),
+    };
+    let bytes = test.to_bytes().unwrap();
+
+    // Split the data at every possible point so that values span both halves of the chain.
+    for at in 0..bytes.len() {
+        let chain = bytes.slice(..at).chain(bytes.slice(at..));
+        let mut reader = BufReader::new(chain);
+        let decoded = Test::deserialize(&mut reader).unwrap();
+
+        assert_eq!(decoded.id, test.id);
+        assert_eq!(decoded.name, test.name);
+        assert!(matches!(decoded.name.into_inner(), Cow::Owned(_)));
+        assert_eq!(decoded.values, test.values);
+        assert_eq!(decoded.rest, test.rest);
+        assert_eq!(reader.position(), bytes.len());
+        assert_eq!(reader.into_inner().remaining(), 0);
+    }
+
+    // Reading the rest of a `Bytes` buffer into owned bytes does not copy it.
+    let data = Bytes::from_static(&[1, 2, 3, 4]);
+    let mut reader = BufReader::new(data.clone());
+    let rest = OwnedUnsizedBytes::deserialize(&mut reader).unwrap();
+    assert_eq!(rest.as_ptr(), data.as_ptr());
+
+    let decoded = Test::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert!(matches!(decoded.name.into_inner(), Cow::Borrowed(_)));
+
+    let err = Test::deserialize(&mut BufReader::new(bytes.slice(..6))).unwrap_err();
+    assert_eq!(err.offset(), 5);
+}
+
+///
+/// This test tests that the framed codec decodes frames that arrive a byte at a time or several at once,
+/// rejects frames over the maximum frame size, and does not leave partial frames behind when encoding
+/// fails.
+///
+#[test]
+fn test_codec() {
+    use binary::codec::{CodecError, FrameCodec};
+    use binary::datatypes::{VarU32, I16, U16};
+    use binary::error::{DecodeError, EncodeError};
+    use binary::prefixed::OwnedStr;
+    use binary_derive::Binary;
+    use byteorder::{BE, LE};
+    use std::io::Write;
+    use tokio_bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    #[derive(Debug, Binary)]
+    struct Message {
+        id: U16<LE>,
+        text: OwnedStr<VarU32>,
+    }
+
+    let mut codec = FrameCodec::<VarU32, Message>::new();
+    let mut encoded = BytesMut::new();
+
+    for (id, text) in [(1, "hello"), (2, "a".repeat(200).as_str())] {
+        let message = Message {
+            id: U16::new(id),
+            text: OwnedStr::new(text.to_string()),
+        };
+        codec.encode(message, &mut encoded).unwrap();
+    }
+    // The second frame is longer than 127 bytes, so its prefix takes two bytes.
+    assert_eq!(encoded[0], 8);
+    assert_eq!(&encoded[9..11], &[0xCC, 0x01]);
+
+    let mut src = BytesMut::new();
+    let mut decoded = Vec::new();
+
+    for byte in encoded.iter() {
+        src.extend_from_slice(&[*byte]);
+
+        if let Some(message) = codec.decode(&mut src).unwrap() {
+            decoded.push(message);
+        }
+    }
+    assert!(src.is_empty());
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].id.0, 1);
+    assert_eq!(decoded[0].text.as_str(), "hello");
+    assert_eq!(decoded[1].id.0, 2);
+    assert_eq!(decoded[1].text.len(), 200);
+
+    let mut src = encoded.clone();
+    assert_eq!(codec.decode(&mut src).unwrap().unwrap().id.0, 1);
+    assert_eq!(codec.decode(&mut src).unwrap().unwrap().id.0, 2);
+    assert!(codec.decode(&mut src).unwrap().is_none());
+
+    // Frames over the maximum are rejected from their prefix alone, both ways.
+    let mut codec = FrameCodec::<U16<BE>, Message>::with_max_frame_len(16);
+    let mut src = BytesMut::from(&[0x01, 0x00][..]);
+    assert!(matches!(
+        codec.decode(&mut src),
+        Err(CodecError::FrameTooLarge { len: 256, max: 16 })
+    ));
+
+    let message = Message {
+        id: U16::new(3),
+        text: OwnedStr::new("x".repeat(32)),
+    };
+    assert!(matches!(
+        codec.encode(message, &mut BytesMut::new()),
+        Err(CodecError::FrameTooLarge { len: 35, max: 16 })
+    ));
+
+    // The message has to fill the frame exactly.
+    let mut src = BytesMut::from(&[0x00, 0x05, 0x01, 0x00, 0x01, b'a', 0xFF][..]);
+    assert!(matches!(
+        codec.decode(&mut src),
+        Err(CodecError::Decode(DecodeError::TrailingBytes {
+            offset: 4,
+            remaining: 1
+        }))
+    ));
+
+    // A message that fails to encode leaves nothing behind, so the next frame is written as it should be.
+    #[derive(Debug, Binary)]
+    struct Short {
+        id: U16<LE>,
+        text: OwnedStr<I16<LE>>,
+    }
+
+    let mut codec = FrameCodec::<VarU32, Short>::new();
+    let mut dst = BytesMut::new();
+    let message = Short {
+        id: U16::new(1),
+        text: OwnedStr::new("a".repeat(i16::MAX as usize + 1)),
+    };
+    assert!(matches!(
+        codec.encode(message, &mut dst),
+        Err(CodecError::Encode(EncodeError::LengthOverflow { .. }))
+    ));
+    assert!(dst.is_empty());
+
+    let message = Short {
+        id: U16::new(2),
+        text: OwnedStr::new("b"),
+    };
+    codec.encode(message, &mut dst).unwrap();
+    assert_eq!(&dst[..], &[5, 2, 0, 1, 0, b'b'][..]);
+}
+
+///
+/// This test tests the encoding and decoding of fixed length arrays, tuples and smart pointers.
+///
+#[test]
+fn test_containers() {
+    use binary::datatypes::{VarU32, F32, U8};
+    use binary::prefixed::Str;
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::LE;
+    use std::borrow::Cow;
+    use std::io::Write;
+    use std::marker::PhantomData;
+    use std::sync::Arc;
+
+    #[derive(Debug, Binary)]
+    struct Test<'a> {
+        uuid: [U8; 16],
+        position: (F32<LE>, F32<LE>, F32<LE>),
+        name: Box<Str<'a, VarU32>>,
+        shared: Arc<VarU32>,
+        cow: Cow<'a, VarU32>,
+        marker: PhantomData<u64>,
+    }
+
+    let id = VarU32::new(300);
+    let test = Test {
+        uuid: std::array::from_fn(|i| U8::new(i as u8)),
+        position: (F32::new(1.0), F32::new(-2.0), F32::new(0.5)),
+        name: Box::new(Str::new("Steve")),
+        shared: Arc::new(VarU32::new(7)),
+        cow: Cow::Borrowed(&id),
+        marker: PhantomData,
+    };
+    let bytes = test.to_bytes().unwrap();
+
+    assert_eq!(test.encoded_len(), bytes.len());
+    assert_eq!(bytes.len(), 16 + 12 + 6 + 1 + 2);
+    assert_eq!(bytes[..16], (0..16).collect::<Vec<u8>>());
+
+    let decoded = Test::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.uuid, test.uuid);
+    assert_eq!(decoded.position, test.position);
+    assert_eq!(decoded.name, test.name);
+    assert_eq!(decoded.shared, test.shared);
+    assert!(matches!(decoded.cow, Cow::Owned(VarU32(300))));
+
+    // Tuples with all twelve elements are supported as well.
+    let tuple = (
+        U8::new(1),
+        U8::new(2),
+        U8::new(3),
+        U8::new(4),
+        U8::new(5),
+        U8::new(6),
+        U8::new(7),
+        U8::new(8),
+        U8::new(9),
+        U8::new(10),
+        U8::new(11),
+        VarU32::new(300),
+    );
+    let bytes = tuple.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 0xAC, 0x02]);
+    assert_eq!(
+        <(U8, U8, U8, U8, U8, U8, U8, U8, U8, U8, U8, VarU32)>::deserialize(&mut SliceReader::new(
+            &bytes
+        ))
+        .unwrap(),
+        tuple
+    );
+
+    // A truncated array fails instead of returning a partially filled one.
+    assert!(<[U8; 16]>::deserialize(&mut SliceReader::new(&[0; 15])).is_err());
+}
+
+///
+/// This test tests plain Rust primitives, strings and vectors whose encoding is selected with the
+/// `#[binary(...)]` field attributes.
+///
+#[test]
+fn test_primitives() {
+    use binary::datatypes::{VarI32, VarU32, VarU64, F32, I32, U16};
+    use binary::prefixed::{Array, Str};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::{BE, LE};
+    use std::io::Write;
+
+    #[derive(Debug, PartialEq, Binary)]
+    struct Plain {
+        flag: bool,
+        byte: u8,
+        #[binary(le)]
+        short: u16,
+        #[binary(be)]
+        int: i32,
+        #[binary(varint)]
+        id: i32,
+        #[binary(varint)]
+        time: u64,
+        #[binary(le)]
+        speed: f32,
+        #[binary(prefix = "VarU32")]
+        name: String,
+        #[binary(prefix = "U16")]
+        data: Vec<u8>,
+        #[binary(prefix = "VarU32", varint)]
+        ids: Vec<u32>,
+    }
+
+    #[derive(Debug, Binary)]
+    struct Wrapped<'a> {
+        flag: bool,
+        byte: u8,
+        short: U16<LE>,
+        int: I32<BE>,
+        id: VarI32,
+        time: VarU64,
+        speed: F32<LE>,
+        name: Str<'a, VarU32>,
+        data: Array<'a, u8, U16<LE>>,
+        ids: Array<'a, VarU32, VarU32>,
+    }
+
+    let plain = Plain {
+        flag: true,
+        byte: 7,
+        short: 0x1234,
+        int: -2,
+        id: -300,
+        time: 1 << 40,
+        speed: 1.5,
+        name: String::from("Steve"),
+        data: vec![1, 2, 3],
+        ids: vec![1, 300, u32::MAX],
+    };
+    let wrapped = Wrapped {
+        flag: true,
+        byte: 7,
+        short: U16::new(0x1234),
+        int: I32::new(-2),
+        id: VarI32::new(-300),
+        time: VarU64::new(1 << 40),
+        speed: F32::new(1.5),
+        name: Str::new("Steve"),
+        data: Array::new(vec![1, 2, 3]),
+        ids: Array::new(vec![
+            VarU32::new(1),
+            VarU32::new(300),
+            VarU32::new(u32::MAX),
+        ]),
+    };
+
+    // The attributes select the same encoding as the datatypes.
+    let bytes = plain.to_bytes().unwrap();
+    assert_eq!(bytes, wrapped.to_bytes().unwrap());
+    assert_eq!(plain.encoded_len(), bytes.len());
+
+    let decoded = Plain::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded, plain);
+}
+
+///
+/// This test tests that varints round trip at the limits of their types, and that strict decoding rejects
+/// over-long and overflowing encodings that lenient decoding accepts.
+///
+#[test]
+fn test_strict_varint() {
+    use binary::datatypes::{Strict, VarI32, VarI64, VarU32, VarU64};
+    use binary::error::DecodeError;
+    use binary::prefixed::Str;
+    use binary::reader::{SliceReader, VarIntMode};
+    use binary::Binary;
+
+    fn round_trip<'a, B: Binary<'a> + PartialEq>(val: B, storage: &'a mut Vec<u8>) {
+        val.serialize(storage).unwrap();
+
+        let mut reader = SliceReader::new(storage);
+        reader.set_varint_mode(VarIntMode::Strict);
+        assert_eq!(B::deserialize(&mut reader).unwrap(), val);
+    }
+
+    for n in [0, 1, -1, 63, -64, i32::MAX, i32::MIN] {
+        round_trip(VarI32::new(n), &mut Vec::new());
+        round_trip(VarU32::new(n as u32), &mut Vec::new());
+    }
+    for n in [0, 1, -1, i64::MAX, i64::MIN] {
+        round_trip(VarI64::new(n), &mut Vec::new());
+        round_trip(VarU64::new(n as u64), &mut Vec::new());
+    }
+
+    let lenient = |data: &[u8]| VarU32::deserialize(&mut SliceReader::new(data));
+    let strict = |data: &[u8]| Strict::<VarU32>::deserialize(&mut SliceReader::new(data));
+
+    // Over-long encoding of zero.
+    assert_eq!(lenient(&[0x80, 0x00]).unwrap().0, 0);
+    assert_eq!(
+        strict(&[0x80, 0x00]).unwrap_err(),
+        DecodeError::NonCanonicalVarInt { offset: 0 }
+    );
+    assert_eq!(strict(&[0x00]).unwrap().0 .0, 0);
+
+    // The last byte sets bits beyond the 32 bits of the type.
+    let overflowing = [0xFF, 0xFF, 0xFF, 0xFF, 0x1F];
+    assert_eq!(lenient(&overflowing).unwrap().0, u32::MAX);
+    assert_eq!(
+        strict(&overflowing).unwrap_err(),
+        DecodeError::VarIntOverflow {
+            offset: 0,
+            max_bytes: 5
+        }
+    );
+    assert_eq!(
+        strict(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]).unwrap().0 .0,
+        u32::MAX
+    );
+
+    let mut max = [0xFF; 10];
+    max[9] = 0x01;
+    assert_eq!(
+        Strict::<VarU64>::deserialize(&mut SliceReader::new(&max))
+            .unwrap()
+            .0
+             .0,
+        u64::MAX
+    );
+    max[9] = 0x03;
+    assert!(Strict::<VarU64>::deserialize(&mut SliceReader::new(&max)).is_err());
+
+    // The mode of the reader applies to the plain varints, including the ones used as prefixes.
+    let data = [0x81, 0x00, b'a'];
+    let mut reader = SliceReader::new(&data);
+    reader.set_varint_mode(VarIntMode::Strict);
+    assert_eq!(
+        Str::<VarU32>::deserialize(&mut reader).unwrap_err(),
+        DecodeError::NonCanonicalVarInt { offset: 0 }
+    );
+    assert_eq!(
+        Str::<VarU32>::deserialize(&mut SliceReader::new(&data))
+            .unwrap()
+            .as_ref(),
+        "a"
+    );
+    assert!(Str::<Strict<VarU32>>::deserialize(&mut SliceReader::new(&data)).is_err());
+}
+
+///
+/// This test tests that varints decode the same from contiguous slices, which take the fast path, and
+/// from buffers split into single bytes, which are read byte by byte, in both decoding modes.
+///
+#[test]
+fn test_varint_paths() {
+    use binary::datatypes::{VarI32, VarU32, VarU64};
+    use binary::reader::{BufReader, Reader, SliceReader, VarIntMode};
+    use binary::Binary;
+    use bytes::{Buf, Bytes};
+
+    /// Reference encoding that writes the varint one byte at a time.
+    fn encode(mut val: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        while val >= 0x80 {
+            data.push(val as u8 | 0x80);
+            val >>= 7;
+        }
+
+        data.push(val as u8);
+        data
+    }
+
+    fn decode_both<B: for<'a> Binary<'a> + PartialEq>(data: &[u8]) {
+        for mode in [VarIntMode::Lenient, VarIntMode::Strict] {
+            // Padding after the varint lets the fast path read a whole word.
+            let mut padded = data.to_vec();
+            padded.extend_from_slice(&[0xFF; 8]);
+            let mut fast = SliceReader::new(&padded);
+            fast.set_varint_mode(mode);
+
+            let mut chain: Box<dyn Buf> = Box::new(Bytes::new());
+            for b in data {
+                chain = Box::new(chain.chain(Bytes::copy_from_slice(&[*b])));
+            }
+            let mut slow = BufReader::new(chain);
+            slow.set_varint_mode(mode);
+
+            let fast_val = B::deserialize(&mut fast);
+            let slow_val = B::deserialize(&mut slow);
+            assert_eq!(fast_val, slow_val, "{:x?} in {:?}", data, mode);
+
+            if fast_val.is_ok() {
+                assert_eq!(fast.position(), data.len());
+                assert_eq!(slow.position(), data.len());
+            }
+        }
+    }
+
+    let mut values = vec![0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX];
+    for shift in 0..64 {
+        values.push(1 << shift);
+        values.push((1 << shift) - 1);
+    }
+
+    for val in values {
+        let data = encode(val);
+        assert_eq!(VarU64::new(val).to_bytes().unwrap(), data);
+        assert_eq!(
+            VarU32::new(val as u32).to_bytes().unwrap(),
+            encode(val as u32 as u64)
+        );
+
+        decode_both::<VarU64>(&data);
+        decode_both::<VarU32>(&data);
+        decode_both::<VarI32>(&data);
+    }
+
+    // Over-long and overflowing encodings are handled the same by both paths.
+    decode_both::<VarU32>(&[0x80, 0x80, 0x00]);
+    decode_both::<VarU32>(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F]);
+    decode_both::<VarU32>(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+    decode_both::<VarU64>(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]);
+}
+
+///
+/// This test tests the encoding of the composite datatypes and the conversions and arithmetic on them.
+///
+#[test]
+fn test_composite() {
+    use binary::composite::{BlockPos, RotationByte, Uuid, Vec2, Vec3};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+
+    let pos = BlockPos::new(-1, -64, 300);
+    let bytes = pos.to_bytes().unwrap();
+    assert_eq!(
+        &bytes[..],
+        &[0x01, 0xC0, 0xFF, 0xFF, 0xFF, 0x0F, 0xD8, 0x04][..]
+    );
+    assert_eq!(pos.encoded_len(), bytes.len());
+    assert_eq!(
+        BlockPos::deserialize(&mut SliceReader::new(&bytes)).unwrap(),
+        pos
+    );
+
+    let vec = Vec3::new(1.0, -2.5, 0.25);
+    let bytes = vec.to_bytes().unwrap();
+    assert_eq!(&bytes[..4], &1.0f32.to_le_bytes());
+    assert_eq!(
+        Vec3::deserialize(&mut SliceReader::new(&bytes)).unwrap(),
+        vec
+    );
+
+    let vec2 = Vec2::new(90.0, 45.0);
+    let bytes = vec2.to_bytes().unwrap();
+    assert_eq!(bytes.len(), 8);
+    assert_eq!(
+        Vec2::deserialize(&mut SliceReader::new(&bytes)).unwrap(),
+        vec2
+    );
+
+    let uuid = Uuid::from_bytes([
+        0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f, 0xe0,
+        0xc8,
+    ]);
+    assert_eq!(uuid.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+    let bytes = uuid.to_bytes().unwrap();
+    // Each half is written as a little endian u64, the most significant half first.
+    assert_eq!(
+        &bytes[..],
+        &[
+            0x6f, 0x42, 0xb1, 0x10, 0x44, 0x50, 0xe5, 0x67, 0xc8, 0xe0, 0x5f, 0x0e, 0x68, 0xbb,
+            0x47, 0x92
+        ][..]
+    );
+    assert_eq!(
+        Uuid::deserialize(&mut SliceReader::new(&bytes)).unwrap(),
+        uuid
+    );
+    assert_eq!(Uuid::from_u64_pair(1, 2).as_u64_pair(), (1, 2));
+
+    assert_eq!(RotationByte::from_degrees(90.0).0, 64);
+    assert_eq!(RotationByte::from_degrees(-90.0).0, 192);
+    assert_eq!(RotationByte::from_degrees(360.0).0, 0);
+    assert_eq!(RotationByte::new(128).degrees(), 180.0);
+    assert_eq!(RotationByte::new(64).to_bytes().unwrap()[..], [64]);
+
+    // Arithmetic and conversions.
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    assert_eq!(a + a, a * 2.0);
+    assert_eq!(a - a, Vec3::ZERO);
+    assert_eq!(-a / 2.0, Vec3::new(-0.5, -1.0, -1.5));
+    assert_eq!(a.dot(Vec3::new(1.0, 0.0, 0.0)), 1.0);
+    assert_eq!(
+        Vec3::new(1.0, 0.0, 0.0).cross(Vec3::new(0.0, 1.0, 0.0)),
+        Vec3::new(0.0, 0.0, 1.0)
+    );
+    assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+    assert_eq!(Vec2::new(3.0, 4.0).normalize(), Vec2::new(0.6, 0.8));
+    assert_eq!(
+        Vec3::new(-0.5, 64.9, 3.0).block_pos(),
+        BlockPos::new(-1, 64, 3)
+    );
+    assert_eq!(BlockPos::new(1, 2, 3).center(), Vec3::new(1.5, 2.5, 3.5));
+    assert_eq!(
+        BlockPos::new(1, 2, 3).offset(0, -1, 0),
+        BlockPos::from([1, 1, 3])
+    );
+
+    let mut pos = BlockPos::from((1, 2, 3));
+    pos += BlockPos::new(1, 1, 1);
+    pos *= 2;
+    assert_eq!(<[i32; 3]>::from(pos), [4, 6, 8]);
+}
+
+///
+/// This test tests the encoding and decoding of NBT in all three flavours.
+///
+#[test]
+fn test_nbt() {
+    use binary::datatypes::VarU32;
+    use binary::nbt::{BigEndian, Compound, LittleEndian, Nbt, NetworkLittleEndian, Tag};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    let mut compound = Compound::new();
+    compound.insert("a", 300i32);
+
+    // The root compound, its name, then the int named "a" and the end tag.
+    assert_eq!(
+        &Nbt::<LittleEndian>::compound(compound.clone())
+            .to_bytes()
+            .unwrap()[..],
+        &[10, 0, 0, 3, 1, 0, b'a', 0x2C, 0x01, 0, 0, 0][..]
+    );
+    assert_eq!(
+        &Nbt::<BigEndian>::compound(compound.clone())
+            .to_bytes()
+            .unwrap()[..],
+        &[10, 0, 0, 3, 0, 1, b'a', 0, 0, 0x01, 0x2C, 0][..]
+    );
+    assert_eq!(
+        &Nbt::<NetworkLittleEndian>::compound(compound)
+            .to_bytes()
+            .unwrap()[..],
+        &[10, 0, 3, 1, b'a', 0xD8, 0x04, 0][..]
+    );
+
+    let mut inner = Compound::new();
+    inner.insert("bytes", Tag::ByteArray(vec![1, 2, 3].into()));
+    inner.insert("empty", Vec::<Tag>::new());
+
+    let mut compound = Compound::new();
+    compound.insert("name", "Steve");
+    compound.insert("onGround", true);
+    compound.insert("health", 20.0f32);
+    compound.insert("time", 1i64 << 40);
+    compound.insert("motion", vec![Tag::Double(0.5), Tag::Double(-0.5)]);
+    compound.insert("ints", vec![1, -1]);
+    compound.insert("inner", inner);
+
+    let nbt = Nbt::<NetworkLittleEndian>::compound(compound.clone());
+    let bytes = nbt.to_bytes().unwrap();
+    assert_eq!(nbt.encoded_len(), bytes.len());
+
+    let decoded = Nbt::<NetworkLittleEndian>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded, nbt);
+
+    // Strings and byte arrays borrow from the buffer.
+    let decoded = decoded.tag.as_compound().unwrap();
+    assert!(matches!(
+        decoded.get("name"),
+        Some(Tag::String(Cow::Borrowed("Steve")))
+    ));
+    let inner = decoded.get("inner").unwrap().as_compound().unwrap();
+    assert!(matches!(
+        inner.get("bytes"),
+        Some(Tag::ByteArray(Cow::Borrowed(&[1, 2, 3])))
+    ));
+    assert_eq!(decoded.get("health").unwrap().as_float(), Some(20.0));
+    assert_eq!(
+        decoded.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["name", "onGround", "health", "time", "motion", "ints", "inner"]
+    );
+
+    // Inserting replaces the tag in place.
+    assert_eq!(compound.insert("health", 10.0f32), Some(Tag::Float(20.0)));
+    assert_eq!(compound.iter().nth(2).unwrap().0, "health");
+    assert_eq!(compound.remove("name"), Some(Tag::from("Steve")));
+    assert_eq!(compound.len(), 6);
+
+    // Lists must contain a single type of tag.
+    let mixed = Nbt::<LittleEndian>::new("", vec![Tag::Int(1), Tag::Long(1)]);
+    assert!(mixed.to_bytes().is_err());
+
+    // Roots can be embedded into packets as fields.
+    #[derive(Debug, Binary)]
+    struct BlockActorData<'a> {
+        id: VarU32,
+        data: Nbt<'a, NetworkLittleEndian>,
+    }
+
+    let mut data = Compound::new();
+    data.insert("id", "Chest");
+
+    let packet = BlockActorData {
+        id: VarU32::new(7),
+        data: Nbt::compound(data),
+    };
+    let bytes = packet.to_bytes().unwrap();
+    let decoded = BlockActorData::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.id.0, 7);
+    assert_eq!(decoded.data, packet.data);
+    assert_eq!(
+        decoded.data.into_owned().tag.as_compound().unwrap().len(),
+        1
+    );
+}
+
+///
+/// This test tests the mapping of structs to and from NBT compounds with the `Nbt` derive.
+///
+#[test]
+fn test_nbt_derive() {
+    use binary::datatypes::VarU32;
+    use binary::error::{DecodeError, PathSegment};
+    use binary::nbt::{
+        Compound, FromTag, LittleEndian, Nbt, NetworkLittleEndian, Tag, TagError, ToTag,
+    };
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::{Binary, Nbt};
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    #[derive(Debug, PartialEq, Nbt)]
+    struct Enchantment {
+        id: i16,
+        lvl: i16,
+    }
+
+    #[derive(Debug, PartialEq, Nbt)]
+    struct ItemData<'a> {
+        #[nbt(rename = "Name")]
+        name: Cow<'a, str>,
+        #[nbt(rename = "Damage")]
+        damage: Option<i16>,
+        #[nbt(rename = "Unbreakable", default)]
+        unbreakable: bool,
+        #[nbt(default = "default_count")]
+        count: i8,
+        #[nbt(rename = "ench", default)]
+        enchantments: Vec<Enchantment>,
+        lore: Option<Vec<String>>,
+        #[nbt(skip)]
+        cached: u32,
+    }
+
+    fn default_count() -> i8 {
+        1
+    }
+
+    let item = ItemData {
+        name: Cow::Borrowed("minecraft:diamond_sword"),
+        damage: None,
+        unbreakable: true,
+        count: 1,
+        enchantments: vec![Enchantment { id: 9, lvl: 5 }],
+        lore: Some(vec![String::from("Sharp")]),
+        cached: 0,
+    };
+
+    let tag = item.to_tag();
+    let compound = tag.as_compound().unwrap();
+    assert_eq!(
+        compound.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["Name", "Unbreakable", "count", "ench", "lore"]
+    );
+    assert_eq!(compound.get("Unbreakable"), Some(&Tag::Byte(1)));
+
+    // Structs are written as a root compound with the network encoding by default.
+    let bytes = item.to_bytes().unwrap();
+    assert_eq!(
+        &bytes,
+        &Nbt::<NetworkLittleEndian>::compound(compound.clone())
+            .to_bytes()
+            .unwrap()
+    );
+
+    let decoded = ItemData::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded, item);
+    assert!(matches!(decoded.name, Cow::Borrowed(_)));
+
+    // Missing tags become `None` or the default.
+    let mut compound = Compound::new();
+    compound.insert("Name", "minecraft:stick");
+    let decoded = ItemData::from_tag(Tag::Compound(compound)).unwrap();
+    assert_eq!(decoded.damage, None);
+    assert!(!decoded.unbreakable);
+    assert_eq!(decoded.count, 1);
+    assert!(decoded.enchantments.is_empty());
+
+    // Tags of the wrong type and missing fields are reported with the field.
+    let mut compound = Compound::new();
+    compound.insert("Name", "minecraft:stick");
+    compound.insert("ench", vec![Tag::Compound(Compound::new())]);
+    assert_eq!(
+        ItemData::from_tag(Tag::Compound(compound)),
+        Err(TagError::InField {
+            field: "ench",
+            error: Box::new(TagError::MissingField { field: "id" }),
+        })
+    );
+    assert_eq!(
+        ItemData::from_tag(Tag::Int(1)),
+        Err(TagError::UnexpectedType {
+            expected: 10,
+            found: 3,
+        })
+    );
+
+    let bytes = Nbt::<NetworkLittleEndian>::compound(Compound::new())
+        .to_bytes()
+        .unwrap();
+    assert_eq!(
+        ItemData::deserialize(&mut SliceReader::new(&bytes)).unwrap_err(),
+        DecodeError::Nbt {
+            offset: 0,
+            error: TagError::MissingField { field: "Name" },
+        }
+    );
+
+    // Errors in the fields have the same path as the errors of the fields of `Binary` types.
+    let mut enchantment = Compound::new();
+    enchantment.insert("id", 9i16);
+    enchantment.insert("lvl", "V");
+    let mut compound = Compound::new();
+    compound.insert("Name", "minecraft:stick");
+    compound.insert("ench", vec![Tag::Compound(enchantment)]);
+    let bytes = Nbt::<NetworkLittleEndian>::compound(compound)
+        .to_bytes()
+        .unwrap();
+    let err = ItemData::deserialize(&mut SliceReader::new(&bytes)).unwrap_err();
+    assert_eq!(
+        err,
+        DecodeError::InField {
+            ty: Some("ItemData"),
+            path: vec![PathSegment::Field("ench"), PathSegment::Field("lvl")],
+            error: Box::new(DecodeError::Nbt {
+                offset: 0,
+                error: TagError::UnexpectedType {
+                    expected: 2,
+                    found: 8
+                },
+            }),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "ItemData.ench.lvl: expected tag of type 2, found 8 in NBT at byte 0"
+    );
+
+    // The encoding can be selected and the structs can be used as packet fields.
+    #[derive(Debug, PartialEq, Nbt)]
+    #[nbt(encoding = "LittleEndian")]
+    struct LevelData {
+        #[nbt(rename = "LevelName")]
+        level_name: String,
+        #[nbt(rename = "RandomSeed")]
+        random_seed: i64,
+    }
+
+    let level = LevelData {
+        level_name: String::from("world"),
+        random_seed: -1,
+    };
+    assert_eq!(
+        level.to_bytes().unwrap(),
+        Nbt::<LittleEndian>::new("", level.to_tag())
+            .to_bytes()
+            .unwrap()
+    );
+
+    #[derive(Debug, Binary)]
+    struct Packet {
+        id: VarU32,
+        level: LevelData,
+    }
+
+    let packet = Packet {
+        id: VarU32::new(1),
+        level,
+    };
+    let bytes = packet.to_bytes().unwrap();
+    let decoded = Packet::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.level, packet.level);
+}
+
+///
+/// This test tests that the array tags are written by the array newtypes and read by them and by vectors.
+///
+#[test]
+fn test_nbt_arrays() {
+    use binary::nbt::{ByteArray, Compound, FromTag, IntArray, LongArray, Tag, ToTag};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Nbt;
+    use std::borrow::Cow;
+
+    #[derive(Debug, PartialEq, Nbt)]
+    struct Chunk<'a> {
+        biomes: ByteArray<'a>,
+        heights: IntArray,
+        blocks: LongArray,
+        palette: Vec<i32>,
+    }
+
+    let chunk = Chunk {
+        biomes: ByteArray(Cow::Borrowed(&[1, 2, 0xFF])),
+        heights: IntArray(vec![64, -1]),
+        blocks: LongArray(vec![i64::MIN, 0, i64::MAX]),
+        palette: vec![3, 4],
+    };
+
+    let tag = chunk.to_tag();
+    let compound = tag.as_compound().unwrap();
+    assert_eq!(
+        compound.get("biomes"),
+        Some(&Tag::ByteArray(Cow::Borrowed(&[1, 2, 0xFF])))
+    );
+    assert_eq!(compound.get("heights"), Some(&Tag::IntArray(vec![64, -1])));
+    assert_eq!(
+        compound.get("blocks"),
+        Some(&Tag::LongArray(vec![i64::MIN, 0, i64::MAX]))
+    );
+    assert_eq!(
+        compound.get("palette"),
+        Some(&Tag::List(vec![Tag::Int(3), Tag::Int(4)]))
+    );
+
+    let bytes = chunk.to_bytes().unwrap();
+    let decoded = Chunk::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded, chunk);
+    assert!(matches!(decoded.biomes.0, Cow::Borrowed(_)));
+
+    // Vectors read the array tags and the newtypes read lists.
+    let mut compound = Compound::new();
+    compound.insert("biomes", vec![Tag::Byte(1), Tag::Byte(-1)]);
+    compound.insert("heights", vec![Tag::Int(64)]);
+    compound.insert("blocks", vec![Tag::Long(-1)]);
+    compound.insert("palette", Tag::IntArray(vec![3, 4]));
+    assert_eq!(
+        Chunk::from_tag(Tag::Compound(compound)).unwrap(),
+        Chunk {
+            biomes: ByteArray(Cow::Owned(vec![1, 0xFF])),
+            heights: IntArray(vec![64]),
+            blocks: LongArray(vec![-1]),
+            palette: vec![3, 4],
+        }
+    );
+    assert_eq!(
+        Vec::<i8>::from_tag(Tag::ByteArray(Cow::Borrowed(&[1, 0xFF]))).unwrap(),
+        [1, -1]
+    );
+    assert_eq!(Vec::<i64>::from_tag(Tag::LongArray(vec![7])).unwrap(), [7]);
+    assert!(Vec::<i64>::from_tag(Tag::IntArray(vec![7])).is_err());
+    assert!(IntArray::from_tag(Tag::LongArray(vec![7])).is_err());
+}
+
+///
+/// This test tests the encoding and decoding of length prefixed bytes and unsized arrays.
+///
+#[test]
+fn test_bytes() {
+    use binary::datatypes::{VarI32, VarU32, U16};
+    use binary::error::DecodeError;
+    use binary::prefixed::{Bytes, OwnedBytes, Str, UnsizedArray, UnsizedBytes};
+    use binary::reader::{BufReader, Limits, SliceReader};
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::LE;
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Skin<'a> {
+        id: Str<'a, VarU32>,
+        image: Bytes<'a, U16<LE>>,
+        layers: UnsizedArray<Str<'a, VarU32>>,
+    }
+
+    let image = [0x89, b'P', b'N', b'G', 0xFF];
+    let skin = Skin {
+        id: Str::new("custom"),
+        image: Bytes::new(&image),
+        layers: UnsizedArray::new(vec![Str::new("hat"), Str::new("cape")]),
+    };
+
+    let bytes = skin.to_bytes().unwrap();
+    assert_eq!(skin.encoded_len(), bytes.len());
+    assert_eq!(&bytes[7..14], &[5, 0, 0x89, b'P', b'N', b'G', 0xFF][..]);
+
+    let decoded = Skin::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert!(matches!(
+        decoded.image.clone().into_inner(),
+        Cow::Borrowed(_)
+    ));
+    assert_eq!(&decoded.image[..], &image[..]);
+    assert_eq!(
+        decoded
+            .layers
+            .iter()
+            .map(|s| s.as_ref())
+            .collect::<Vec<_>>(),
+        ["hat", "cape"]
+    );
+
+    let owned: OwnedBytes<U16<LE>> = decoded.image.into();
+    assert_eq!(
+        owned.to_bytes().unwrap(),
+        Bytes::<U16<LE>>::new(&image).to_bytes().unwrap()
+    );
+    let decoded = OwnedBytes::<U16<LE>>::deserialize(&mut BufReader::new(&bytes[7..14])).unwrap();
+    assert_eq!(decoded, owned);
+
+    // An empty buffer is an empty array.
+    let decoded = UnsizedArray::<VarI32>::deserialize(&mut SliceReader::new(&[])).unwrap();
+    assert!(decoded.is_empty());
+
+    // Elements that do not read any bytes cannot be decoded until the end of the buffer.
+    assert!(
+        UnsizedArray::<std::marker::PhantomData<u8>>::deserialize(&mut SliceReader::new(&[1]))
+            .is_err()
+    );
+
+    // Bytes are printed in hex and truncated.
+    assert_eq!(format!("{:?}", Bytes::<VarU32>::new(&image)), "89504e47ff");
+    let large = vec![0xAB; 1000];
+    assert_eq!(
+        format!("{:?}", OwnedBytes::<VarU32>::new(large)),
+        format!("{}... (1000 bytes)", "ab".repeat(32))
+    );
+    assert_eq!(format!("{:?}", UnsizedBytes::new(&[1, 2])), "[1, 2]");
+
+    // The length of the bytes is limited the same way as the length of strings.
+    let limits = Limits {
+        max_string_len: 4,
+        ..Limits::default()
+    };
+    let data = [5u8, 1, 2, 3, 4, 5];
+    let expected = DecodeError::LengthLimit {
+        offset: 0,
+        len: 5,
+        limit: 4,
+    };
+    assert_eq!(
+        Bytes::<VarU32>::deserialize(&mut SliceReader::with_limits(&data, limits)).unwrap_err(),
+        expected
+    );
+    assert_eq!(
+        OwnedBytes::<VarU32>::deserialize(&mut SliceReader::with_limits(&data, limits))
+            .unwrap_err(),
+        expected
+    );
+}
+
+///
+/// This test tests the encoding and decoding of arrays with their count, or their length in bytes, in
+/// another field with the `[binary(count)]` and `[binary(len_bytes)]` attributes.
+///
+#[test]
+fn test_count_fields() {
+    use binary::datatypes::{VarI64, VarU32, U16, U8};
+    use binary::error::{DecodeError, EncodeError};
+    use binary::prefixed::{Str, UnsizedArray, UnsizedBytes};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::LE;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Entries<'a> {
+        #[binary(varint)]
+        count: u32,
+        name: Str<'a, VarU32>,
+        #[binary(count = "count")]
+        ids: Vec<VarI64>,
+        #[binary(count = "count", le)]
+        weights: Vec<f32>,
+    }
+
+    // The count is written from the length of the arrays, whatever the field holds.
+    let entries = Entries {
+        count: 0,
+        name: Str::new("ab"),
+        ids: vec![VarI64::new(-1), VarI64::new(300)],
+        weights: vec![0.5, 1.5],
+    };
+    let bytes = entries.to_bytes().unwrap();
+    assert_eq!(entries.encoded_len(), bytes.len());
+    assert_eq!(&bytes[..6], &[2, 2, b'a', b'b', 0x01, 0xD8][..]);
+
+    let decoded = Entries::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.count, 2);
+    assert_eq!(decoded.ids, entries.ids);
+    assert_eq!(decoded.weights, entries.weights);
+
+    let mismatched = Entries {
+        weights: vec![0.5],
+        ..entries
+    };
+    assert!(matches!(mismatched.to_bytes(), Err(EncodeError::Custom(_))));
+
+    // The count must fit into the field that holds it.
+    #[derive(Debug, Binary)]
+    struct Small {
+        count: U8,
+        #[binary(count = "count")]
+        values: Vec<U8>,
+    }
+
+    let small = Small {
+        count: U8::new(0),
+        values: vec![U8::new(0); 256],
+    };
+    assert!(matches!(
+        small.to_bytes(),
+        Err(EncodeError::LengthOverflow { len: 256, max: 255 })
+    ));
+    assert_eq!(small.count.0, 0);
+
+    // Fields can have the names of the locals of the derived code.
+    #[derive(Debug, PartialEq, Binary)]
+    struct Counted {
+        offset: U8,
+        #[binary(count = "offset")]
+        items: Vec<U8>,
+    }
+
+    let counted = Counted {
+        offset: U8::new(0),
+        items: vec![U8::new(7), U8::new(8)],
+    };
+    let bytes = counted.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[2, 7, 8][..]);
+    let decoded = Counted::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.items, counted.items);
+
+    #[derive(Debug, PartialEq, Binary)]
+    struct Locals<'a> {
+        buf: U8,
+        len: U8,
+        tag: U8,
+        #[binary(count = "len")]
+        element: Vec<U8>,
+        #[binary(if = "tag.0 == 1")]
+        val: Option<U8>,
+        #[binary(len_bytes = "buf.0 as usize")]
+        err: UnsizedBytes<'a>,
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    enum Variants {
+        Locals { buf: U8, tag: U8 },
+    }
+
+    let locals = Locals {
+        buf: U8::new(1),
+        len: U8::new(0),
+        tag: U8::new(1),
+        element: vec![U8::new(2)],
+        val: Some(U8::new(3)),
+        err: UnsizedBytes::new(&[4]),
+    };
+    let bytes = locals.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[1, 1, 1, 2, 3, 4][..]);
+    assert_eq!(
+        Locals::deserialize(&mut SliceReader::new(&bytes)).unwrap(),
+        Locals {
+            len: U8::new(1),
+            ..locals
+        }
+    );
+
+    let variants = Variants::Locals {
+        buf: U8::new(5),
+        tag: U8::new(6),
+    };
+    let bytes = variants.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[0, 5, 6][..]);
+    assert_eq!(
+        Variants::deserialize(&mut SliceReader::new(&bytes)).unwrap(),
+        variants
+    );
+
+    // A length in bytes computed from a count of bits, like in the RakNet frame header.
+    #[derive(Debug, Binary)]
+    struct Frame<'a> {
+        #[binary(be)]
+        bit_len: u16,
+        flags: U8,
+        #[binary(len_bytes = "(bit_len as usize).div_ceil(8)")]
+        body: UnsizedBytes<'a>,
+        trailer: U8,
+    }
+
+    let data = [0x00, 0x14, 0x40, 1, 2, 3, 0xFF];
+    let frame = Frame::deserialize(&mut SliceReader::new(&data)).unwrap();
+    assert_eq!(&frame.body[..], &[1, 2, 3][..]);
+    assert_eq!(frame.trailer.0, 0xFF);
+    assert_eq!(&frame.to_bytes().unwrap()[..], &data[..]);
+
+    // The body cannot be longer than the buffer.
+    let err = Frame::deserialize(&mut SliceReader::new(&[0x00, 0xFF, 0x40, 1])).unwrap_err();
+    assert!(matches!(
+        err.root(),
+        DecodeError::UnexpectedEof { offset: 3 }
+    ));
+
+    // A length in bytes held by a field is written from the encoded length.
+    #[derive(Debug, Binary)]
+    struct Blob {
+        size: U16<LE>,
+        #[binary(len_bytes = "size")]
+        values: UnsizedArray<VarU32>,
+    }
+
+    let blob = Blob {
+        size: U16::new(0),
+        values: UnsizedArray::new(vec![VarU32::new(1), VarU32::new(300)]),
+    };
+    let bytes = blob.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[3, 0, 1, 0xAC, 0x02][..]);
+
+    let decoded = Blob::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.size.0, 3);
+    assert_eq!(decoded.values.len(), 2);
+
+    // An element that ends past the length is an error rather than reading into the next field.
+    let err = Blob::deserialize(&mut SliceReader::new(&[2, 0, 1, 0xAC, 0x02])).unwrap_err();
+    assert!(matches!(err.root(), DecodeError::UnexpectedEof { .. }));
+}
+
+///
+/// This test tests the encoding and decoding of fields that are present depending on the earlier fields
+/// with the `[binary(if)]` attribute.
+///
+#[test]
+fn test_conditional_fields() {
+    use binary::datatypes::{VarU32, U8};
+    use binary::error::EncodeError;
+    use binary::prefixed::Str;
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Text<'a> {
+        kind: U8,
+        #[binary(if = "kind.0 == 1 || kind.0 == 7")]
+        source: Option<Str<'a, VarU32>>,
+        message: Str<'a, VarU32>,
+        #[binary(if = "kind.0 == 2", prefix = "VarU32")]
+        parameters: Option<Vec<Str<'a, VarU32>>>,
+    }
+
+    let chat = Text {
+        kind: U8::new(1),
+        source: Some(Str::new("Steve")),
+        message: Str::new("hi"),
+        parameters: None,
+    };
+    let bytes = chat.to_bytes().unwrap();
+    assert_eq!(chat.encoded_len(), bytes.len());
+    // No `bool` is written before the source.
+    assert_eq!(&bytes[..3], &[1, 5, b'S'][..]);
+
+    let decoded = Text::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.source.as_deref().map(|s| s.as_ref()), Some("Steve"));
+    assert!(decoded.parameters.is_none());
+
+    let translation = Text {
+        kind: U8::new(2),
+        source: None,
+        message: Str::new("death.attack.generic"),
+        parameters: Some(vec![Str::new("Steve")]),
+    };
+    let bytes = translation.to_bytes().unwrap();
+    assert_eq!(translation.encoded_len(), bytes.len());
+
+    let decoded = Text::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert!(decoded.source.is_none());
+    assert_eq!(decoded.parameters.unwrap()[0].as_ref(), "Steve");
+
+    // Conditions on bits of flags, with an encoding for the value.
+    #[derive(Debug, Binary)]
+    struct Input {
+        #[binary(varint)]
+        flags: u64,
+        #[binary(if = "flags & 1 != 0", le)]
+        yaw: Option<f32>,
+        #[binary(if = "flags & 2 != 0", varint)]
+        tick: Option<i64>,
+    }
+
+    let input = Input {
+        flags: 2,
+        yaw: None,
+        tick: Some(-1),
+    };
+    let bytes = input.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[2, 1][..]);
+
+    let decoded = Input::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.yaw, None);
+    assert_eq!(decoded.tick, Some(-1));
+    assert_eq!(decoded.flags, 2);
+
+    // A field that is present according to the flags but missing from the buffer is an error.
+    assert!(Input::deserialize(&mut SliceReader::new(&[3, 0, 0])).is_err());
+
+    // Values that would not be read back are not written.
+    #[derive(Debug, Binary)]
+    struct Cond {
+        flag: bool,
+        #[binary(if = "*flag")]
+        val: Option<U8>,
+    }
+
+    for cond in [
+        Cond {
+            flag: true,
+            val: None,
+        },
+        Cond {
+            flag: false,
+            val: Some(U8::new(1)),
+        },
+    ] {
+        assert!(matches!(
+            cond.to_bytes(),
+            Err(EncodeError::Custom(message)) if message == "`val` must be `Some` exactly when `* flag` is true"
+        ));
+    }
+
+    // The fields are referenced without being cloned.
+    #[derive(Debug, Binary)]
+    struct Flags(U8);
+
+    #[derive(Debug, Binary)]
+    struct Flagged {
+        flags: Flags,
+        #[binary(if = "flags.0 .0 & 1 != 0")]
+        val: Option<U8>,
+    }
+
+    let flagged = Flagged {
+        flags: Flags(U8::new(1)),
+        val: Some(U8::new(2)),
+    };
+    let bytes = flagged.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[1, 2][..]);
+    let decoded = Flagged::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.val, flagged.val);
+
+    // The condition sees the fields as they are decoded, such as a count written from a length.
+    #[derive(Debug, Binary)]
+    struct Counted {
+        len: U8,
+        #[binary(count = "len")]
+        items: Vec<U8>,
+        #[binary(if = "len.0 > 0")]
+        first: Option<U8>,
+    }
+
+    let counted = Counted {
+        len: U8::new(0),
+        items: vec![U8::new(4)],
+        first: Some(U8::new(4)),
+    };
+    let bytes = counted.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[1, 4, 4][..]);
+
+    let decoded = Counted::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.len.0, 1);
+    assert_eq!(decoded.first, counted.first);
+}
+
+///
+/// This test tests fields encoded by custom functions with the `[binary(with)]`,
+/// `[binary(serialize_with)]` and `[binary(deserialize_with)]` attributes.
+///
+#[test]
+fn test_custom_functions() {
+    use binary::datatypes::U16;
+    use binary::error::{DecodeError, EncodeError};
+    use binary::reader::{Reader, SliceReader};
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::BE;
+    use std::io::Write;
+
+    /// Colors written as a `#rrggbb` string.
+    mod hex_color {
+        use binary::datatypes::VarU32;
+        use binary::error::{DecodeError, EncodeError};
+        use binary::prefixed::Str;
+        use binary::reader::Reader;
+        use binary::Binary;
+        use std::io::Write;
+
+        pub fn serialize(val: &u32, buf: &mut impl Write) -> Result<(), EncodeError> {
+            Str::<VarU32>::new(&format!("#{:06x}", val)).serialize(buf)
+        }
+
+        pub fn deserialize<'a>(buf: &mut impl Reader<'a>) -> Result<u32, DecodeError> {
+            let offset = buf.position();
+            let val = Str::<VarU32>::deserialize(buf)?;
+
+            val.strip_prefix('#')
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(DecodeError::Custom {
+                    offset,
+                    message: String::from("invalid color"),
+                })
+        }
+    }
+
+    /// Two 4-bit values packed into the first byte.
+    fn write_nibbles(val: &(u8, u8), buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_all(&[val.0 << 4 | val.1 & 0x0F])?;
+        Ok(())
+    }
+
+    fn read_nibbles<'a>(buf: &mut impl Reader<'a>) -> Result<(u8, u8), DecodeError> {
+        let byte = buf.read_u8()?;
+        Ok((byte >> 4, byte & 0x0F))
+    }
+
+    #[derive(Debug, Binary)]
+    struct Banner {
+        #[binary(serialize_with = "write_nibbles", deserialize_with = "read_nibbles")]
+        nibbles: (u8, u8),
+        #[binary(with = "hex_color")]
+        color: u32,
+        pattern: U16<BE>,
+    }
+
+    let banner = Banner {
+        nibbles: (3, 9),
+        color: 0xFF8000,
+        pattern: U16::new(5),
+    };
+    let bytes = banner.to_bytes().unwrap();
+    assert_eq!(banner.encoded_len(), bytes.len());
+    assert_eq!(&bytes[..3], &[0x39, 7, b'#'][..]);
+    assert_eq!(&bytes[3..9], b"ff8000");
+
+    let decoded = Banner::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.nibbles, (3, 9));
+    assert_eq!(decoded.color, 0xFF8000);
+    assert_eq!(decoded.pattern.0, 5);
+
+    let err = Banner::deserialize(&mut SliceReader::new(&[0x39, 2, b'#', b'x', 0, 5])).unwrap_err();
+    assert!(matches!(err.root(), DecodeError::Custom { offset: 1, .. }));
+}
+
+///
+/// This test tests that the derive compiles for generic structs and enums, with the default bounds and
+/// with the `[binary(bound)]` attribute, and that it reports errors for bad input such as duplicate
+/// discriminants or packet ids.
+///
+#[test]
+fn test_derive_bounds() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/generic_struct.rs");
+    t.pass("tests/ui/generic_enum.rs");
+    t.pass("tests/ui/custom_bound.rs");
+    t.compile_fail("tests/ui/not_binary.rs");
+    t.compile_fail("tests/ui/misspelled_bound.rs");
+    t.compile_fail("tests/ui/duplicate_discriminant.rs");
+    t.compile_fail("tests/ui/discriminant_overflow.rs");
+    t.compile_fail("tests/ui/duplicate_packet_id.rs");
+    t.compile_fail("tests/ui/packet_id_overflow.rs");
+}
+
+///
+/// This test tests the `[binary(tag)]` attribute of enums, with negative, 64-bit and custom tag types.
+///
+#[test]
+fn test_enum_tags() {
+    use binary::datatypes::{VarU32, VarU64, I16, I8, U16, U8};
+    use binary::encoding::Discriminant;
+    use binary::error::DecodeError;
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use byteorder::BE;
+    use std::io::Write;
+
+    #[derive(Debug, PartialEq, Binary)]
+    #[binary(tag = I8)]
+    enum Signed {
+        #[variant(tag = -1)]
+        Unknown,
+        Known(U8),
+        #[variant(tag = -128)]
+        Min,
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    #[binary(tag = VarU64)]
+    enum Wide {
+        Small,
+        #[variant(tag = 1099511627776)]
+        Large {
+            val: VarU32,
+        },
+        Next,
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    #[binary(tag = I16<BE>)]
+    enum BigEndian {
+        #[variant(tag = -2)]
+        Left,
+        Right,
+    }
+
+    /// Packet ids, which only use the low 12 bits.
+    #[derive(Debug, Binary)]
+    struct PacketId(U16<BE>);
+
+    impl Discriminant for PacketId {
+        const MIN: i128 = 0;
+        const MAX: i128 = 0xFFF;
+
+        fn from_discriminant(disc: i128) -> Option<Self> {
+            (0..0x1000)
+                .contains(&disc)
+                .then(|| Self(U16::new(disc as u16)))
+        }
+
+        fn to_discriminant(&self) -> i128 {
+            (self.0 .0 & 0xFFF) as i128
+        }
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    #[binary(tag = PacketId)]
+    enum Packet {
+        #[variant(tag = 0x123)]
+        Login,
+    }
+
+    fn round_trip<'a, T: Binary<'a> + PartialEq>(val: T, bytes: &'a [u8]) {
+        assert_eq!(&val.to_bytes().unwrap()[..], bytes);
+        assert_eq!(val.encoded_len(), bytes.len());
+        assert_eq!(T::deserialize(&mut SliceReader::new(bytes)).unwrap(), val);
+    }
+
+    round_trip(Signed::Unknown, &[0xFF]);
+    round_trip(Signed::Known(U8::new(7)), &[0, 7]);
+    round_trip(Signed::Min, &[0x80]);
+    round_trip(Wide::Small, &[0]);
+    round_trip(
+        Wide::Large {
+            val: VarU32::new(1),
+        },
+        &[0x80, 0x80, 0x80, 0x80, 0x80, 0x20, 1],
+    );
+    round_trip(Wide::Next, &[0x81, 0x80, 0x80, 0x80, 0x80, 0x20]);
+    round_trip(BigEndian::Left, &[0xFF, 0xFE]);
+    round_trip(BigEndian::Right, &[0xFF, 0xFF]);
+    round_trip(Packet::Login, &[0x01, 0x23]);
+    // The custom tag ignores the high bits.
+    assert_eq!(
+        Packet::deserialize(&mut SliceReader::new(&[0xF1, 0x23])).unwrap(),
+        Packet::Login
+    );
+
+    let err = Signed::deserialize(&mut SliceReader::new(&[5])).unwrap_err();
+    assert!(matches!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 0,
+            value: 5
+        }
+    ));
+    let err = Wide::deserialize(&mut SliceReader::new(&[0xFF; 10])).unwrap_err();
+    assert!(matches!(err, DecodeError::VarIntOverflow { .. }));
+    assert!(I16::<BE>::from_discriminant(i16::MIN as i128 - 1).is_none());
+}
+
+///
+/// This test tests that enums use their Rust discriminants, and that the `[binary(other)]` variant keeps
+/// the discriminants and data of the variants that are not known.
+///
+#[test]
+fn test_enum_discriminants() {
+    use binary::datatypes::{VarI32, VarU32, I8, U8};
+    use binary::error::DecodeError;
+    use binary::prefixed::{Str, UnsizedBytes};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use std::io::Write;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Binary)]
+    #[binary(tag = I8)]
+    enum Difficulty {
+        Unknown = -1,
+        Peaceful,
+        Easy,
+        Hard = 3,
+        Extreme,
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    #[binary(tag = U8)]
+    #[repr(u8)]
+    enum Event<'a> {
+        Chat(Str<'a, VarU32>) = 2,
+        Move {
+            x: VarI32,
+            z: VarI32,
+        },
+        #[binary(other)]
+        Unknown(U8, UnsizedBytes<'a>),
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    enum Mode {
+        Survival,
+        #[variant(tag = 5)]
+        Creative,
+        #[binary(other)]
+        Other {
+            id: VarI32,
+        },
+    }
+
+    fn round_trip<'a, T: Binary<'a> + PartialEq>(val: T, bytes: &'a [u8]) {
+        assert_eq!(&val.to_bytes().unwrap()[..], bytes);
+        assert_eq!(val.encoded_len(), bytes.len());
+        assert_eq!(T::deserialize(&mut SliceReader::new(bytes)).unwrap(), val);
+    }
+
+    round_trip(Difficulty::Unknown, &[0xFF]);
+    round_trip(Difficulty::Peaceful, &[0]);
+    round_trip(Difficulty::Easy, &[1]);
+    round_trip(Difficulty::Hard, &[3]);
+    round_trip(Difficulty::Extreme, &[4]);
+    // The encoded discriminant is the same as the one of the Rust enum.
+    for difficulty in [Difficulty::Unknown, Difficulty::Hard, Difficulty::Extreme] {
+        assert_eq!(difficulty.to_bytes().unwrap()[0] as i8, difficulty as i8);
+    }
+    let err = Difficulty::deserialize(&mut SliceReader::new(&[2])).unwrap_err();
+    assert!(matches!(
+        err,
+        DecodeError::InvalidDiscriminant {
+            offset: 0,
+            value: 2
+        }
+    ));
+
+    round_trip(Event::Chat(Str::new("hi")), &[2, 2, b'h', b'i']);
+    round_trip(
+        Event::Move {
+            x: VarI32::new(1),
+            z: VarI32::new(-1),
+        },
+        &[3, 2, 1],
+    );
+    // Events from newer versions are kept with their data, so that they can be forwarded as they are.
+    let data = [9, 1, 2, 3];
+    let event = Event::deserialize(&mut SliceReader::new(&data)).unwrap();
+    assert_eq!(
+        event,
+        Event::Unknown(U8::new(9), UnsizedBytes::new(&[1, 2, 3]))
+    );
+    assert_eq!(&event.to_bytes().unwrap()[..], &data[..]);
+    assert_eq!(event.encoded_len(), data.len());
+
+    round_trip(Mode::Survival, &[0]);
+    round_trip(Mode::Creative, &[10]);
+    round_trip(
+        Mode::Other {
+            id: VarI32::new(-7),
+        },
+        &[13],
+    );
+}
+
+///
+/// This test tests that the field attributes behave the same on tuple structs and on the fields of enum
+/// variants as on named fields.
+///
+#[test]
+fn test_field_attributes() {
+    use binary::datatypes::{VarU32, U8};
+    use binary::error::{DecodeError, EncodeError};
+    use binary::prefixed::Str;
+    use binary::reader::{Reader, SliceReader};
+    use binary::Binary;
+    use binary_derive::Binary;
+    use std::io::Write;
+
+    fn write_flag(val: &bool, buf: &mut impl Write) -> Result<(), EncodeError> {
+        buf.write_all(&[if *val { b'y' } else { b'n' }])?;
+        Ok(())
+    }
+
+    fn read_flag<'a>(buf: &mut impl Reader<'a>) -> Result<bool, DecodeError> {
+        Ok(buf.read_u8()? == b'y')
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    struct Entry<'a>(
+        #[binary(varint)] u32,
+        #[skip] u64,
+        #[binary(serialize_with = "write_flag", deserialize_with = "read_flag")] bool,
+        #[binary(if = "*_2")] Option<Str<'a, VarU32>>,
+    );
+
+    #[derive(Debug, PartialEq, Binary)]
+    struct Names<'a>(U8, #[binary(count = "_0")] Vec<Str<'a, VarU32>>);
+
+    #[derive(Debug, PartialEq, Binary)]
+    #[binary(tag = U8)]
+    enum Change<'a> {
+        Rename(
+            #[binary(be)] u16,
+            #[binary(serialize_with = "write_flag", deserialize_with = "read_flag")] bool,
+            #[binary(if = "*_1")] Option<Str<'a, VarU32>>,
+        ),
+        Resize {
+            #[binary(le)]
+            width: u16,
+            #[skip]
+            cached: u32,
+            len: U8,
+            #[binary(count = "len", varint)]
+            sizes: Vec<i32>,
+        },
+        #[binary(other)]
+        Unknown(U8, #[binary(prefix = "VarU32")] Vec<u8>),
+    }
+
+    fn round_trip<'a, T: Binary<'a> + PartialEq>(val: T, bytes: &'a [u8]) {
+        assert_eq!(&val.to_bytes().unwrap()[..], bytes);
+        assert_eq!(val.encoded_len(), bytes.len());
+        assert_eq!(T::deserialize(&mut SliceReader::new(bytes)).unwrap(), val);
+    }
+
+    round_trip(
+        Entry(300, 0, true, Some(Str::new("a"))),
+        &[0xAC, 2, b'y', 1, b'a'],
+    );
+    round_trip(Entry(1, 0, false, None), &[1, b'n']);
+    // Skipped fields are not written and are decoded as their default value.
+    let entry = Entry(1, 99, false, None);
+    assert_eq!(&entry.to_bytes().unwrap()[..], &[1, b'n']);
+    assert_eq!(entry.1, 99);
+
+    round_trip(
+        Names(U8::new(2), vec![Str::new("a"), Str::new("b")]),
+        &[2, 1, b'a', 1, b'b'],
+    );
+
+    round_trip(
+        Change::Rename(0x102, true, Some(Str::new("x"))),
+        &[0, 1, 2, b'y', 1, b'x'],
+    );
+    round_trip(Change::Rename(7, false, None), &[0, 0, 7, b'n']);
+    round_trip(
+        Change::Resize {
+            width: 0x102,
+            cached: 0,
+            len: U8::new(2),
+            sizes: vec![-1, 1],
+        },
+        &[1, 2, 1, 2, 1, 2],
+    );
+    // The count is written from the length of the field that it counts.
+    let resize = Change::Resize {
+        width: 1,
+        cached: 5,
+        len: U8::new(3),
+        sizes: vec![],
+    };
+    assert_eq!(&resize.to_bytes().unwrap()[..], &[1, 1, 0, 0]);
+    round_trip(Change::Unknown(U8::new(9), vec![1, 2]), &[9, 2, 1, 2]);
+}
+
+///
+/// This test tests that errors from derived types lead to the field that failed to decode, through
+/// nested structs, arrays, options and enum variants.
+///
+#[test]
+fn test_error_context() {
+    use binary::datatypes::{VarI32, VarU32, U8};
+    use binary::error::{DecodeError, PathSegment};
+    use binary::prefixed::{Array, Str};
+    use binary::reader::SliceReader;
+    use binary::Binary;
+    use binary_derive::Binary;
+    use std::io::Write;
+
+    #[derive(Debug, Binary)]
+    struct Position {
+        x: VarI32,
+        y: VarI32,
+    }
+
+    #[derive(Debug, Binary)]
+    struct Settings {
+        seed: VarI32,
+        spawn: Option<Position>,
+    }
+
+    #[derive(Debug, Binary)]
+    #[binary(tag = U8)]
+    enum Action<'a> {
+        Jump,
+        Move { to: Position },
+        Say(Str<'a, VarU32>),
+    }
+
+    #[derive(Debug, Binary)]
+    struct StartGame<'a> {
+        id: VarU32,
+        settings: Settings,
+        actions: Array<'a, Action<'a>, VarU32>,
+    }
+
+    #[derive(Debug, PartialEq, Binary)]
+    struct Names<'a>(U8, #[binary(count = "_0")] Vec<Str<'a, VarU32>>);
+
+    let err = StartGame::deserialize(&mut SliceReader::new(&[1, 2, 1, 4])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "StartGame.settings.spawn.y: unexpected EOF at byte 4"
+    );
+    assert_eq!(err.root(), &DecodeError::UnexpectedEof { offset: 4 });
+    assert_eq!(err.offset(), 4);
+    // Reporters that print the chain of sources print the wrapped error once.
+    fn report(err: &dyn std::error::Error) -> String {
+        let mut report = err.to_string();
+        let mut source = err.source();
+
+        while let Some(err) = source {
+            report.push_str(&format!(": {}", err));
+            source = err.source();
+        }
+
+        report
+    }
+    assert_eq!(
+        report(&err),
+        "StartGame.settings.spawn.y: unexpected EOF at byte 4"
+    );
+
+    let data = [1, 2, 0, 2, 0, 1, 6];
+    let err = StartGame::deserialize(&mut SliceReader::new(&data)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "StartGame.actions[1]::Move.to.y: unexpected EOF at byte 7"
+    );
+    assert!(matches!(
+        err,
+        DecodeError::InField {
+            ty: Some("StartGame"),
+            ref path,
+            ..
+        } if path[..3] == [PathSegment::Field("actions"), PathSegment::Index(1), PathSegment::Variant("Move")]
+    ));
+
+    // Errors that are not in a field are wrapped by the field that the value is decoded into.
+    let err = StartGame::deserialize(&mut SliceReader::new(&[1, 2, 0, 1, 7])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "StartGame.actions[0]: unexpected enum discriminant 7 at byte 4"
+    );
+
+    let err = Names::deserialize(&mut SliceReader::new(&[2, 1, b'a', 5, b'b'])).unwrap_err();
+    assert_eq!(err.to_string(), "Names.1[1]: unexpected EOF at byte 4");
+
+    // The error is still converted to the kind of the error it wraps.
+    let err = std::io::Error::from(err);
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+///
+/// This test tests the packet header, the `Packet` derive and the dispatch of packets by id with a
+/// registry, including packets with ids that are not registered.
+///
+#[test]
+fn test_packets() {
+    use binary::datatypes::{VarI32, VarU32, U8};
+    use binary::error::{DecodeError, EncodeError};
+    use binary::packet::{Framed, Header, Packet, Registry};
+    use binary::prefixed::Str;
+    use binary::reader::{Reader, SliceReader};
+    use binary::Binary;
+    use binary_derive::{registry, Binary, Packet};
+    use std::io::Write;
+
+    #[derive(Debug, Binary, Packet)]
+    #[packet(id = 0x01)]
+    struct Login<'a> {
+        protocol: VarI32,
+        name: Str<'a, VarU32>,
+    }
+
+    #[derive(Debug, Binary, Packet)]
+    #[packet(id = 0x09)]
+    struct Text<'a> {
+        kind: U8,
+        message: Str<'a, VarU32>,
+    }
+
+    #[derive(Debug, Binary, Packet)]
+    #[packet(id = 0x3FF)]
+    struct Last;
+
+    registry! {
+        /// Packets that the client sends.
+        pub enum GamePacket<'a> {
+            Login(Login<'a>),
+            Text(Text<'a>),
+            Last(Last),
+        }
+    }
+
+    assert_eq!(Login::ID, 1);
+    assert_eq!(Text::ID, 9);
+
+    // The sub-client ids are held in the bits after the id.
+    let header = Header {
+        id: 0x09,
+        sender: 1,
+        target: 2,
+    };
+    assert_eq!(&header.to_bytes().unwrap()[..], &[0x89, 0x48]);
+    assert_eq!(header.encoded_len(), 2);
+    assert_eq!(
+        Header::deserialize(&mut SliceReader::new(&[0x89, 0x48])).unwrap(),
+        header
+    );
+    assert_eq!(&Header::new(0x3FF).to_bytes().unwrap()[..], &[0xFF, 0x07]);
+    assert!(matches!(
+        Header::new(0x400).to_bytes(),
+        Err(EncodeError::Custom(_))
+    ));
+
+    let framed = Framed {
+        sender: 0,
+        target: 3,
+        packet: GamePacket::from(Text {
+            kind: U8::new(1),
+            message: Str::new("hi"),
+        }),
+    };
+    let bytes = framed.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[0x89, 0x60, 1, 2, b'h', b'i']);
+    assert_eq!(framed.encoded_len(), bytes.len());
+
+    let decoded = Framed::<GamePacket>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.target, 3);
+    match decoded.packet {
+        GamePacket::Text(text) => assert_eq!(&*text.message, "hi"),
+        packet => panic!("unexpected packet {:?}", packet),
+    }
+
+    let bytes = Framed::new(GamePacket::from(Login {
+        protocol: VarI32::new(686),
+        name: Str::new("Steve"),
+    }))
+    .to_bytes()
+    .unwrap();
+    let decoded = Framed::<GamePacket>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.packet.id(), 1);
+    assert!(
+        matches!(decoded.packet, GamePacket::Login(Login { ref name, .. }) if &**name == "Steve")
+    );
+    let decoded = GamePacket::decode(0x3FF, &mut SliceReader::new(&[])).unwrap();
+    assert!(matches!(decoded, GamePacket::Last(Last)));
+
+    // Packets with ids that are not registered keep their data, and are written back the same way.
+    let data = [0x05, 1, 2, 3];
+    let decoded = Framed::<GamePacket>::deserialize(&mut SliceReader::new(&data)).unwrap();
+    match &decoded.packet {
+        GamePacket::Unknown(packet) => {
+            assert_eq!(packet.id, 5);
+            assert_eq!(&packet.data[..], &[1, 2, 3]);
+        }
+        packet => panic!("unexpected packet {:?}", packet),
+    }
+    assert_eq!(&decoded.to_bytes().unwrap()[..], &data[..]);
+
+    // The data left after a registered packet is only an error with `deny_trailing_bytes`.
+    registry! {
+        #[derive(Debug)]
+        #[registry(deny_trailing_bytes)]
+        enum StrictPacket<'a> {
+            Text(Text<'a>),
+        }
+    }
+
+    let data = [0x09, 1, 2, b'h', b'i', 0xFF];
+    let mut reader = SliceReader::new(&data);
+    let decoded = Framed::<GamePacket>::deserialize(&mut reader).unwrap();
+    assert!(
+        matches!(decoded.packet, GamePacket::Text(Text { ref message, .. }) if &**message == "hi")
+    );
+    assert_eq!(reader.remaining(), 1);
+    let mut reader = SliceReader::new(&data[1..]);
+    assert!(matches!(
+        GamePacket::decode(9, &mut reader),
+        Ok(GamePacket::Text(_))
+    ));
+    assert_eq!(reader.remaining(), 1);
+    let mut reader = SliceReader::new(&data[1..]);
+    assert!(matches!(
+        StrictPacket::decode(9, &mut reader),
+        Err(DecodeError::TrailingBytes {
+            offset: 4,
+            remaining: 1
+        })
+    ));
+    assert!(matches!(
+        Framed::<StrictPacket>::deserialize(&mut SliceReader::new(&data)),
+        Err(DecodeError::TrailingBytes {
+            offset: 5,
+            remaining: 1
+        })
+    ));
+    let decoded = Framed::<StrictPacket>::deserialize(&mut SliceReader::new(&data[..5])).unwrap();
+    assert!(matches!(decoded.packet, StrictPacket::Text(_)));
+    let decoded = Framed::<StrictPacket>::deserialize(&mut SliceReader::new(&[0x05, 1])).unwrap();
+    assert!(matches!(decoded.packet, StrictPacket::Unknown(_)));
+}
@@ -0,0 +1,41 @@
+use binary::datatypes::VarU32;
+use binary::reader::SliceReader;
+use binary::Binary;
+use binary_derive::Binary;
+use std::io::Write;
+use std::marker::PhantomData;
+
+/// Marker types that are not `Binary` themselves.
+#[derive(Debug)]
+struct Login;
+
+// Without the override `Login` would have to implement `Binary`.
+#[derive(Debug, Binary)]
+#[binary(bound = "T: std::fmt::Debug")]
+struct Id<T> {
+    id: VarU32,
+    marker: PhantomData<T>,
+}
+
+#[derive(Debug, Binary)]
+#[binary(bound = "T: Binary<'a> + Clone")]
+struct Cloned<'a, T> {
+    inner: T,
+    name: binary::prefixed::Str<'a, VarU32>,
+}
+
+fn main() {
+    let id = Id::<Login> {
+        id: VarU32::new(1),
+        marker: PhantomData,
+    };
+    let bytes = id.to_bytes().unwrap();
+    let _ = Id::<Login>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+
+    let cloned = Cloned {
+        inner: VarU32::new(1),
+        name: binary::prefixed::Str::new("cloned"),
+    };
+    let bytes = cloned.to_bytes().unwrap();
+    let _ = Cloned::<VarU32>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+}
@@ -0,0 +1,13 @@
+use binary::datatypes::U8;
+use binary_derive::Binary;
+use std::io::Write;
+
+#[derive(Debug, Binary)]
+#[binary(tag = U8)]
+enum Action {
+    Jump,
+    #[variant(tag = 256)]
+    Sneak,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use binary_derive::Binary;
+
+#[derive(Debug, Binary)]
+enum Action {
+    Jump = 1,
+    #[variant(tag = 1)]
+    Sneak,
+}
+
+fn main() {}
@@ -0,0 +1,25 @@
+use binary::datatypes::VarU32;
+use binary::prefixed::Str;
+use binary_derive::{registry, Binary, Packet};
+use std::io::Write;
+
+#[derive(Debug, Binary, Packet)]
+#[packet(id = 0x09)]
+struct Text<'a> {
+    message: Str<'a, VarU32>,
+}
+
+#[derive(Debug, Binary, Packet)]
+#[packet(id = 0x09)]
+struct Chat<'a> {
+    message: Str<'a, VarU32>,
+}
+
+registry! {
+    enum GamePacket<'a> {
+        Text(Text<'a>),
+        Chat(Chat<'a>),
+    }
+}
+
+fn main() {}
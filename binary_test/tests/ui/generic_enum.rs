@@ -0,0 +1,20 @@
+use binary::datatypes::{VarI64, U8};
+use binary::reader::SliceReader;
+use binary::Binary;
+use binary_derive::Binary;
+use std::io::Write;
+
+#[derive(Debug, Binary)]
+#[data(datatype = "U8")]
+enum Either<L, R> {
+    Left(L),
+    Right { value: R },
+}
+
+fn main() {
+    let either = Either::<U8, VarI64>::Right {
+        value: VarI64::new(-1),
+    };
+    let bytes = either.to_bytes().unwrap();
+    let _ = Either::<U8, VarI64>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+}
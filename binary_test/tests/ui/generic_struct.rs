@@ -0,0 +1,36 @@
+use binary::datatypes::{VarU32, U16};
+use binary::reader::SliceReader;
+use binary::Binary;
+use binary_derive::Binary;
+use byteorder::LE;
+use std::io::Write;
+
+#[derive(Debug, Binary)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+#[derive(Debug, Binary)]
+struct Pair<'a, A, B> {
+    first: A,
+    second: Option<B>,
+    name: binary::prefixed::Str<'a, VarU32>,
+}
+
+fn main() {
+    let wrapper = Wrapper {
+        inner: U16::<LE>::new(1),
+    };
+    let bytes = wrapper.to_bytes().unwrap();
+    let _ = Wrapper::<U16<LE>>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+
+    let pair = Pair {
+        first: VarU32::new(1),
+        second: Some(Wrapper {
+            inner: VarU32::new(2),
+        }),
+        name: binary::prefixed::Str::new("pair"),
+    };
+    let bytes = pair.to_bytes().unwrap();
+    let _ = Pair::<VarU32, Wrapper<VarU32>>::deserialize(&mut SliceReader::new(&bytes)).unwrap();
+}
@@ -0,0 +1,9 @@
+use binary_derive::Binary;
+
+#[derive(Debug, Binary)]
+#[binary(bounds = "T: Binary<'a>")]
+struct Wrapper<T> {
+    inner: T,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use binary::Binary;
+use binary_derive::Binary;
+use std::io::Write;
+
+#[derive(Debug, Binary)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+#[derive(Debug)]
+struct NotBinary;
+
+fn main() {
+    let wrapper = Wrapper { inner: NotBinary };
+    let _ = wrapper.to_bytes();
+}
@@ -0,0 +1,7 @@
+use binary_derive::Packet;
+
+#[derive(Packet)]
+#[packet(id = 0x400)]
+struct Large;
+
+fn main() {}